@@ -1,13 +1,20 @@
 use {
     anyhow::Result,
-    cargo_list::{Crates, expanduser},
-    clap::{Parser, ValueEnum, builder::TypedValueParser},
+    cargo_list::{
+        Action, Config, Crates, Event, Job, JobResult, Manifest, Options, Runner, Status,
+        advisory_db, cache_dir, expanduser, log_dir, snapshot_dir,
+    },
+    clap::{
+        ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum, builder::TypedValueParser,
+        parser::ValueSource,
+    },
     clap_cargo::style::CLAP_STYLING,
     indexmap::IndexSet,
     rayon::prelude::*,
+    regex::RegexSet,
     spinners::{Spinner, Spinners},
     sprint::{Command, Shell},
-    std::collections::BTreeMap,
+    std::{collections::BTreeMap, time::Duration},
     veg::colored::{ColoredString, Colorize, Veg},
 };
 
@@ -35,6 +42,98 @@ impl Kind {
     }
 }
 
+impl From<&cargo_list::Kind> for Kind {
+    fn from(kind: &cargo_list::Kind) -> Kind {
+        match kind {
+            cargo_list::Kind::External => External,
+            cargo_list::Kind::Git => Git,
+            cargo_list::Kind::Local => Local,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+
+#[derive(Clone, ValueEnum)]
+enum Backend {
+    /// Sparse registry index protocol
+    Index,
+
+    /// crates.io web API
+    Api,
+}
+
+impl Backend {
+    fn into(&self) -> cargo_list::Backend {
+        match self {
+            Backend::Index => cargo_list::Backend::Index,
+            Backend::Api => cargo_list::Backend::Api,
+        }
+    }
+}
+
+impl From<cargo_list::Backend> for Backend {
+    fn from(backend: cargo_list::Backend) -> Backend {
+        match backend {
+            cargo_list::Backend::Index => Backend::Index,
+            cargo_list::Backend::Api => Backend::Api,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+
+#[derive(Clone, PartialEq, ValueEnum)]
+enum Installer {
+    /// Build from source via `cargo install`
+    Cargo,
+
+    /// Install prebuilt binaries via `cargo binstall` (falls back to `cargo
+    /// install` for crates with features or a non-release profile)
+    Binstall,
+}
+
+impl Installer {
+    fn into(&self) -> cargo_list::Installer {
+        match self {
+            Installer::Cargo => cargo_list::Installer::Cargo,
+            Installer::Binstall => cargo_list::Installer::Binstall,
+        }
+    }
+}
+
+impl From<cargo_list::Installer> for Installer {
+    fn from(installer: cargo_list::Installer) -> Installer {
+        match installer {
+            cargo_list::Installer::Cargo => Installer::Cargo,
+            cargo_list::Installer::Binstall => Installer::Binstall,
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+
+#[derive(Clone, ValueEnum)]
+enum UpdateType {
+    Patch,
+    Minor,
+    Major,
+    Prerelease,
+    Ahead,
+}
+
+impl UpdateType {
+    fn into(&self) -> cargo_list::Update {
+        match self {
+            UpdateType::Patch => cargo_list::Update::Patch,
+            UpdateType::Minor => cargo_list::Update::Minor,
+            UpdateType::Major => cargo_list::Update::Major,
+            UpdateType::Prerelease => cargo_list::Update::Prerelease,
+            UpdateType::Ahead => cargo_list::Update::Ahead,
+        }
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 
 /// List and update installed crates
@@ -70,18 +169,31 @@ struct List {
     all_kinds: bool,
 
     /// Hide up-to-date crates
-    #[arg(short, long)]
+    #[arg(short, long, overrides_with = "no_outdated")]
     outdated: bool,
 
+    /// Show up-to-date crates (overrides the config file)
+    #[arg(long, overrides_with = "outdated")]
+    no_outdated: bool,
+
     /// Ignore version requirements
-    #[arg(short = 'I')]
+    #[arg(short = 'I', overrides_with = "no_ignore_req")]
     ignore_req: bool,
 
+    /// Respect version requirements (overrides the config file)
+    #[arg(long, overrides_with = "ignore_req")]
+    no_ignore_req: bool,
+
     /// Consider a crate to be outdated if compiled with a Rust version
     /// different than the active toolchain
-    #[arg(short = 'R')]
+    #[arg(short = 'R', overrides_with = "no_outdated_rust")]
     outdated_rust: bool,
 
+    /// Ignore the Rust version crates were compiled with (overrides the
+    /// config file)
+    #[arg(long, overrides_with = "outdated_rust")]
+    no_outdated_rust: bool,
+
     /// Update outdated crates
     #[arg(short, long)]
     update: bool,
@@ -90,6 +202,121 @@ struct List {
     #[arg(short = 'n', long)]
     dry_run: bool,
 
+    /// Update up to N crates concurrently (with the output of each saved to
+    /// a log file)
+    #[arg(short, long, value_name = "N", default_value_t = 1)]
+    jobs: usize,
+
+    /// Continue updating crates after a failed update (default)
+    #[arg(long, overrides_with = "fail_fast")]
+    keep_going: bool,
+
+    /// Stop updating crates after a failed update
+    #[arg(long, overrides_with = "keep_going")]
+    fail_fast: bool,
+
+    /// Shared target directory for concurrent updates [default: a temporary
+    /// one for each crate]
+    #[arg(long, value_name = "PATH")]
+    target_dir: Option<String>,
+
+    /// Installer used to update crates
+    #[arg(long, value_enum, default_value = "cargo")]
+    installer: Installer,
+
+    /// Update crates with `--locked`
+    #[arg(long, overrides_with = "no_locked")]
+    locked: bool,
+
+    /// Update crates without `--locked` (overrides the config file)
+    #[arg(long, overrides_with = "locked")]
+    no_locked: bool,
+
+    /// Update crates with all their binaries instead of only the installed
+    /// ones (`--bin`)
+    #[arg(long, overrides_with = "keep_bins")]
+    all_bins: bool,
+
+    /// Update crates with only their installed binaries (overrides the
+    /// config file)
+    #[arg(long, overrides_with = "all_bins")]
+    keep_bins: bool,
+
+    /// Backend used to get available versions
+    #[arg(short = 'b', long, value_enum, default_value = "index")]
+    backend: Backend,
+
+    /// Resolve available versions from the local registry index cache
+    /// (may be stale)
+    #[arg(long, overrides_with = "online")]
+    offline: bool,
+
+    /// Resolve available versions from the registry (overrides the config
+    /// file)
+    #[arg(long, visible_alias = "no-offline", overrides_with = "offline")]
+    online: bool,
+
+    /// Reuse cached registry responses younger than this without revalidating
+    #[arg(long, value_name = "SECONDS", default_value_t = 300)]
+    ttl: u64,
+
+    /// Ignore cached registry responses
+    #[arg(long)]
+    refresh: bool,
+
+    /// Maximum number of concurrent registry requests [default: number of
+    /// CPUs]
+    #[arg(long, value_name = "N")]
+    max_requests: Option<usize>,
+
+    /// Retry failed registry requests
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    retries: u32,
+
+    /// Timeout for each registry request
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    timeout: u64,
+
+    /// Timeout for all registry requests
+    #[arg(long, value_name = "SECONDS")]
+    total_timeout: Option<u64>,
+
+    /// Fail if any crate fails to process instead of reporting it as an
+    /// error
+    #[arg(long)]
+    strict: bool,
+
+    /// Cap updates at the newest version the active toolchain can build per
+    /// its `rust-version`
+    #[arg(long)]
+    msrv: bool,
+
+    /// Move yanked installs to the nearest available version (even if pinned)
+    #[arg(long)]
+    yanked: bool,
+
+    /// Only list/update external crates with the given update type(s)
+    #[arg(long, value_enum, value_name = "TYPE", value_delimiter = ',')]
+    only: Vec<UpdateType>,
+
+    /// Check installed versions against the security advisory database
+    #[arg(long)]
+    audit: bool,
+
+    /// Local clone of the `rustsec/advisory-db` repository (implies `--audit`)
+    /// [default: $CARGO_HOME/advisory-db]
+    #[arg(long, value_name = "PATH")]
+    advisory_db: Option<String>,
+
+    /// Only list/update crates with security advisories (implies `--audit`)
+    #[arg(long)]
+    vulnerable: bool,
+
+    /// Consider a local crate outdated if its source files were modified
+    /// after it was installed
+    #[arg(long)]
+    mtime: bool,
+
     /**
     Cargo install metadata file
     (falls back to `~/.cargo/.crates2.json` if `$CARGO_HOME` is unset)
@@ -105,9 +332,120 @@ struct List {
     #[arg(short, long)]
     readme: bool,
 
+    /// Skip crates matching the given pattern
+    #[arg(short = 'x', long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+
     /// List/update crates matching given pattern(s)
     #[arg(value_name = "PATTERN")]
     include: Vec<String>,
+
+    #[command(subcommand)]
+    command: Option<ListCommand>,
+}
+
+#[derive(clap::Subcommand, Clone)]
+enum ListCommand {
+    /// Export installed crates to a reproducible manifest
+    Export(ExportArgs),
+
+    /// Install, reinstall, or uninstall crates to match a manifest
+    Sync(SyncArgs),
+
+    /// Compare the crates in two cargo install metadata files or manifests
+    Diff(DiffArgs),
+
+    /// Reinstall the versions of crates saved before an update
+    Rollback(RollbackArgs),
+}
+
+#[derive(clap::Args, Clone)]
+struct ExportArgs {
+    /// Manifest format
+    #[arg(short, long, value_enum, default_value = "toml")]
+    format: ManifestFormat,
+
+    /// Write to a file instead of stdout
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<String>,
+
+    /// Export crates matching given pattern(s)
+    #[arg(value_name = "PATTERN")]
+    include: Vec<String>,
+}
+
+#[derive(Clone, ValueEnum)]
+enum ManifestFormat {
+    Toml,
+    Json,
+
+    /// Shell script of `cargo install` commands
+    Sh,
+}
+
+#[derive(clap::Args, Clone)]
+struct SyncArgs {
+    /// Manifest file (JSON if it has a `.json` extension, otherwise TOML)
+    #[arg(value_name = "MANIFEST")]
+    manifest: String,
+
+    /// Uninstall crates that are not in the manifest
+    #[arg(long)]
+    uninstall: bool,
+
+    /// Dry run
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Sync crates matching given pattern(s)
+    #[arg(value_name = "PATTERN")]
+    include: Vec<String>,
+}
+
+#[derive(clap::Args, Clone)]
+struct DiffArgs {
+    /// Output format
+    #[arg(
+        short = 'f',
+        long = "format",
+        value_name = "FORMAT",
+        default_value_t = Markdown,
+        value_parser = clap::builder::PossibleValuesParser::new(
+            ["json", "json-pretty", "md"],
+        ).map(|s| s.parse::<OutputFormat>().unwrap()),
+    )]
+    output_format: OutputFormat,
+
+    /// Cargo install metadata file (`.crates2.json`) or manifest
+    #[arg(value_name = "A")]
+    left: String,
+
+    /// Cargo install metadata file (`.crates2.json`) or manifest
+    #[arg(value_name = "B")]
+    right: String,
+
+    /// Compare crates matching given pattern(s)
+    #[arg(value_name = "PATTERN")]
+    include: Vec<String>,
+}
+
+#[derive(clap::Args, Clone)]
+struct RollbackArgs {
+    /// Roll back a crate to its version before its latest update
+    #[arg(
+        value_name = "CRATE",
+        required_unless_present = "last_run",
+        conflicts_with = "last_run"
+    )]
+    name: Option<String>,
+
+    /// Roll back all crates updated in the last update run
+    #[arg(long)]
+    last_run: bool,
+
+    /// Dry run
+    #[arg(short = 'n', long)]
+    dry_run: bool,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -160,8 +498,9 @@ struct Row {
     pinned: ColoredString,
     installed: ColoredString,
     available: ColoredString,
-    rust: ColoredString,
-    outdated_rust: bool,
+
+    /// Optional columns (Rust, Compatible) shown in the table
+    columns: Vec<ColoredString>,
 }
 
 impl Row {
@@ -171,8 +510,7 @@ impl Row {
         pinned: ColoredString,
         installed: ColoredString,
         available: ColoredString,
-        rust: ColoredString,
-        outdated_rust: bool,
+        columns: Vec<ColoredString>,
     ) -> Box<Row> {
         Box::new(Row {
             number,
@@ -180,8 +518,7 @@ impl Row {
             pinned,
             installed,
             available,
-            rust,
-            outdated_rust,
+            columns,
         })
     }
 }
@@ -195,9 +532,7 @@ impl veg::colored::Table for Row {
             self.installed.clone(),
             self.available.clone(),
         ];
-        if self.outdated_rust {
-            r.push(self.rust.clone());
-        }
+        r.extend(self.columns.iter().cloned());
         r
     }
 }
@@ -205,7 +540,8 @@ impl veg::colored::Table for Row {
 //--------------------------------------------------------------------------------------------------
 
 fn main() -> Result<()> {
-    let Cli::List(cli) = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let Cli::List(mut cli) = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
     if cli.readme {
         #[cfg(unix)]
@@ -215,21 +551,394 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    inner(&cli)
+    let config = Config::load()?;
+    if let Some(m) = matches.subcommand_matches("list") {
+        apply_config(&mut cli, m, &config)?;
+    }
+
+    match &cli.command {
+        Some(ListCommand::Export(args)) => return export_manifest(&cli, args, &config),
+        Some(ListCommand::Sync(args)) => return sync_manifest(&cli, args, &config),
+        Some(ListCommand::Diff(args)) => return diff_manifests(&cli, args),
+        Some(ListCommand::Rollback(args)) => return rollback(&cli, args),
+        None => {}
+    }
+
+    inner(&cli, &config)
+}
+
+/// Export the installed crates of all kinds matching the include and exclude patterns
+fn export_manifest(cli: &List, export: &ExportArgs, config: &Config) -> Result<()> {
+    let installed = Crates::parse(
+        &get_config_path(&cli.config),
+        &export
+            .include
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>(),
+        &Options {
+            strict: cli.strict,
+            policies: config.crates.clone(),
+            exclude: cli.exclude.clone(),
+            ..Default::default()
+        },
+    )?;
+    for (name, c) in installed.crates() {
+        if let Some(error) = &c.error {
+            eprintln!("Skipped `{name}`: {error}");
+        }
+    }
+
+    let manifest = Manifest::from(&installed);
+    let s = match export.format {
+        ManifestFormat::Toml => manifest.to_toml()?,
+        ManifestFormat::Json => manifest.to_json()? + "\n",
+        ManifestFormat::Sh => manifest.to_sh()?,
+    };
+    if let Some(path) = &export.output {
+        std::fs::write(expanduser(path), s)?;
+    } else {
+        print!("{s}");
+    }
+    Ok(())
+}
+
+/// Compare the crates matching the include and exclude patterns in two files
+fn diff_manifests(cli: &List, args: &DiffArgs) -> Result<()> {
+    let left = Manifest::load(&expanduser(&args.left))?;
+    let right = Manifest::load(&expanduser(&args.right))?;
+    let include = RegexSet::new(&args.include)?;
+    let exclude = RegexSet::new(&cli.exclude)?;
+    let mut differences = left.diff(&right);
+    differences.retain(|x| {
+        (args.include.is_empty() || include.is_match(&x.name)) && !exclude.is_match(&x.name)
+    });
+
+    match args.output_format {
+        Json => println!("{}", serde_json::to_string(&differences)?),
+        JsonPretty => println!("{}", serde_json::to_string_pretty(&differences)?),
+        _ => {
+            if differences.is_empty() {
+                println!("{}\n", "**No differences!**".green().bold());
+                return Ok(());
+            }
+            let version = |x: Option<&cargo_list::ManifestCrate>| {
+                x.map_or_else(
+                    || "-".red(),
+                    |x| {
+                        match &x.rev {
+                            Some(rev) => format!("{} ({})", x.version, rev.get(..7).unwrap_or(rev)),
+                            None => x.version.clone(),
+                        }
+                        .normal()
+                    },
+                )
+            };
+            println!(
+                "{}\n",
+                format!("*A: `{}`, B: `{}`*", args.left, args.right).italic()
+            );
+            let mut t = Veg::table("#|Name|A|B|Differences\n-:|-|-|-|-");
+            for (i, x) in differences.iter().enumerate() {
+                let fields = match (&x.left, &x.right) {
+                    (None, _) => "only in B".yellow(),
+                    (_, None) => "only in A".yellow(),
+                    (Some(left), Some(right)) => {
+                        let value = |x: &cargo_list::ManifestCrate, field| {
+                            Some(x.value(field))
+                                .filter(|x| !x.is_empty())
+                                .unwrap_or_else(|| String::from("-"))
+                        };
+                        x.fields
+                            .iter()
+                            .map(|field| {
+                                format!(
+                                    "{field}: {} → {}",
+                                    value(left, field),
+                                    value(right, field),
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join("; ")
+                            .normal()
+                    }
+                };
+                t.push(Row::new(
+                    (i + 1).to_string().normal(),
+                    x.name.normal(),
+                    version(x.left.as_ref()),
+                    version(x.right.as_ref()),
+                    fields,
+                    vec![],
+                ));
+            }
+            println!("{}", t.markdown()?);
+        }
+    }
+    Ok(())
+}
+
+/// Reinstall the versions of crates saved in the latest snapshot (containing the crate)
+fn rollback(cli: &List, args: &RollbackArgs) -> Result<()> {
+    let dir = snapshot_dir().ok_or_else(|| anyhow::anyhow!("Failed to get the data directory"))?;
+    let manifest = Manifest::rollback(&dir, args.name.as_deref())?;
+    let crates = Crates::parse(&get_config_path(&cli.config), &[], &Options::default())?;
+    let installed = Manifest::from(&crates);
+
+    println!("{}\n", "# Plan".magenta().bold());
+    let mut t = Veg::table("#|Name|Pinned|Installed|Rollback\n-:|-|-|-|-");
+    for (i, (name, c)) in manifest.crates.iter().enumerate() {
+        t.push(Row::new(
+            (i + 1).to_string().normal(),
+            name.normal(),
+            c.version_req.as_deref().unwrap_or_default().normal(),
+            installed
+                .crates
+                .get(name)
+                .map(|x| x.version.as_str())
+                .unwrap_or_default()
+                .normal(),
+            c.version.green(),
+            vec![],
+        ));
+    }
+    println!("{}", t.markdown()?);
+
+    println!("{}\n", "# Rollback".magenta().bold());
+    let commands = manifest.install_commands(crates.root.as_deref())?;
+    if args.dry_run {
+        let shell = Shell {
+            dry_run: true,
+            info: String::from("bash"),
+            ..Default::default()
+        };
+        for (name, command) in &commands {
+            println!("{}\n", format!("## {name:?}").yellow().bold());
+            let _ = shell.run(&[Command {
+                command: command.join(" "),
+                ..Default::default()
+            }]);
+        }
+        return Ok(());
+    }
+    let jobs = commands
+        .into_iter()
+        .map(|(name, command)| Job {
+            kind: manifest.crates[&name].kind(),
+            name,
+            command,
+        })
+        .collect::<Vec<_>>();
+    let mut results = vec![];
+    Runner::default().run(&jobs, &mut results, |x| show_progress(&x, false))?;
+    print_results(&results, "Rolled back", "roll back")?;
+    Ok(Runner::check(&results)?)
+}
+
+/// Sync the installed crates matching the include and exclude patterns with a manifest
+fn sync_manifest(cli: &List, args: &SyncArgs, config: &Config) -> Result<()> {
+    let manifest = Manifest::from_path(&expanduser(&args.manifest))?;
+    let installed = Crates::parse(
+        &get_config_path(&cli.config),
+        &[],
+        &Options {
+            strict: cli.strict,
+            ..Default::default()
+        },
+    )?;
+    let include = RegexSet::new(&args.include)?;
+    let exclude = RegexSet::new(&cli.exclude)?;
+    let mut steps = manifest.plan(&installed, args.uninstall)?;
+    steps.retain(|x| {
+        (args.include.is_empty() || include.is_match(&x.name))
+            && !exclude.is_match(&x.name)
+            && !config.crates.get(&x.name).is_some_and(|p| p.ignore)
+    });
+
+    if steps.is_empty() {
+        println!(
+            "{}\n",
+            "**Installed crates match the manifest!**".green().bold()
+        );
+        return Ok(());
+    }
+
+    println!("{}\n", "# Plan".magenta().bold());
+    let mut t = Veg::table("#|Name|Action|Installed|Manifest|Differences\n-:|-|-|-|-|-");
+    for (i, step) in steps.iter().enumerate() {
+        t.push(Row::new(
+            (i + 1).to_string().normal(),
+            step.name.normal(),
+            match step.action {
+                Action::Install => "install".green(),
+                Action::Reinstall => "reinstall".yellow(),
+                Action::Uninstall => "uninstall".red(),
+            },
+            step.installed.as_deref().unwrap_or_default().normal(),
+            step.manifest.as_deref().unwrap_or_default().normal(),
+            vec![step.differences.join(", ").normal()],
+        ));
+    }
+    println!("{}", t.markdown()?);
+
+    println!("{}\n", "# Sync".magenta().bold());
+    if args.dry_run {
+        let shell = Shell {
+            dry_run: true,
+            info: String::from("bash"),
+            ..Default::default()
+        };
+        for step in &steps {
+            println!("{}\n", format!("## {:?}", step.name).yellow().bold());
+            let _ = shell.run(&[Command {
+                command: step.command.join(" "),
+                ..Default::default()
+            }]);
+        }
+        return Ok(());
+    }
+    let jobs = steps
+        .iter()
+        .map(|x| Job {
+            name: x.name.clone(),
+            kind: x.kind.clone(),
+            command: x.command.clone(),
+        })
+        .collect::<Vec<_>>();
+    let mut results = vec![];
+    Runner::default().run(&jobs, &mut results, |x| show_progress(&x, false))?;
+    print_results(&results, "Synced", "sync")?;
+    Ok(Runner::check(&results)?)
+}
+
+/// Use the config file values for arguments not given on the command line
+fn apply_config(cli: &mut List, m: &ArgMatches, config: &Config) -> Result<()> {
+    let unset = |id| m.value_source(id) != Some(ValueSource::CommandLine);
+    if unset("kind")
+        && let Some(kinds) = &config.kinds
+    {
+        cli.kind = kinds.iter().map(Kind::from).collect();
+    }
+    if unset("output_format")
+        && !cli.update
+        && let Some(format) = &config.format
+    {
+        cli.output_format = format.parse().map_err(|e: String| anyhow::anyhow!(e))?;
+    }
+    if unset("outdated") && unset("no_outdated") {
+        cli.outdated = config.outdated.unwrap_or_default();
+    }
+    if unset("ignore_req") && unset("no_ignore_req") {
+        cli.ignore_req = config.ignore_req.unwrap_or_default();
+    }
+    if unset("outdated_rust") && unset("no_outdated_rust") {
+        cli.outdated_rust = config.outdated_rust.unwrap_or_default();
+    }
+    if unset("installer")
+        && let Some(installer) = config.installer
+    {
+        cli.installer = Installer::from(installer);
+    }
+    if unset("locked") && unset("no_locked") {
+        cli.locked = config.locked.unwrap_or_default();
+    }
+    if unset("all_bins") && unset("keep_bins") {
+        cli.all_bins = config.all_bins.unwrap_or_default();
+    }
+    let registry = &config.registry;
+    if unset("backend")
+        && let Some(backend) = registry.backend
+    {
+        cli.backend = Backend::from(backend);
+    }
+    if unset("offline") && unset("online") {
+        cli.offline = registry.offline.unwrap_or_default();
+    }
+    if unset("ttl")
+        && let Some(ttl) = registry.ttl
+    {
+        cli.ttl = ttl;
+    }
+    if unset("max_requests") && registry.max_requests.is_some() {
+        cli.max_requests = registry.max_requests;
+    }
+    if unset("retries")
+        && let Some(retries) = registry.retries
+    {
+        cli.retries = retries;
+    }
+    if unset("timeout")
+        && let Some(timeout) = registry.timeout
+    {
+        cli.timeout = timeout;
+    }
+    if unset("total_timeout") && registry.total_timeout.is_some() {
+        cli.total_timeout = registry.total_timeout;
+    }
+    Ok(())
 }
 
 #[allow(clippy::too_many_lines)]
-fn inner(cli: &List) -> Result<()> {
+fn inner(cli: &List, config: &Config) -> Result<()> {
+    let kinds = if cli.all_kinds {
+        cargo_list::ALL_KINDS.to_vec()
+    } else {
+        cli.kind
+            .par_iter()
+            .map(Kind::into)
+            .collect::<IndexSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>()
+    };
+
+    let audit = cli.audit || cli.vulnerable || cli.advisory_db.is_some();
+
     let mut sp = Spinner::new(Spinners::Line, String::new());
 
-    let installed = Crates::from_include(
+    let installed = Crates::from_options(
         &get_config_path(&cli.config),
         &cli.include.iter().map(String::as_str).collect::<Vec<_>>(),
+        &Options {
+            backend: Backend::into(&cli.backend),
+            cache_dir: cache_dir(),
+            cache_ttl: Duration::from_secs(cli.ttl),
+            refresh: cli.refresh,
+            offline: cli.offline,
+            git: kinds.contains(&cargo_list::Kind::Git),
+            local: kinds.contains(&cargo_list::Kind::Local),
+            local_mtime: cli.mtime,
+            max_requests: cli.max_requests.unwrap_or_default(),
+            retries: cli.retries,
+            timeout: Some(Duration::from_secs(cli.timeout)),
+            total_timeout: cli.total_timeout.map(Duration::from_secs),
+            strict: cli.strict,
+            msrv: cli.msrv,
+            advisory_db: audit.then(|| {
+                cli.advisory_db
+                    .as_deref()
+                    .map_or_else(advisory_db, expanduser)
+            }),
+            registry_url: config.registry_url(),
+            policies: config.crates.clone(),
+            installer: Installer::into(&cli.installer),
+            locked: cli.locked,
+            all_bins: cli.all_bins,
+            exclude: cli.exclude.clone(),
+        },
     )?;
     sp.stop();
     eprint!("\x1b[2K\r");
 
-    let all = installed.crates();
+    let mut all = installed.crates();
+    if cli.vulnerable {
+        all.retain(|_, c| is_vulnerable(c));
+    }
+    if !cli.only.is_empty() {
+        let only = cli.only.iter().map(UpdateType::into).collect::<Vec<_>>();
+        all.retain(|_, c| {
+            c.kind != cargo_list::Kind::External || c.update.is_some_and(|x| only.contains(&x))
+        });
+    }
 
     let external = all
         .par_iter()
@@ -246,22 +955,53 @@ fn inner(cli: &List) -> Result<()> {
     let outdated_pinned = external
         .par_iter()
         .filter_map(|(name, c)| {
-            if !c.outdated && !c.outdated_rust && !c.newer.is_empty() {
+            if !c.outdated && !c.outdated_rust && c.hold.is_none() && !c.newer.is_empty() {
                 Some((*name, *c))
             } else {
                 None
             }
         })
         .collect::<BTreeMap<_, _>>();
+    let outdated_yanked = external
+        .par_iter()
+        .filter_map(|(name, c)| {
+            (c.installed_yanked && !c.outdated && c.replacement.is_some()).then_some((*name, *c))
+        })
+        .collect::<BTreeMap<_, _>>();
+    let outdated_git = all
+        .par_iter()
+        .filter_map(|(name, c)| {
+            (c.kind == cargo_list::Kind::Git && c.outdated).then_some((*name, *c))
+        })
+        .collect::<BTreeMap<_, _>>();
+    let outdated_local = all
+        .par_iter()
+        .filter_map(|(name, c)| {
+            (c.kind == cargo_list::Kind::Local && c.outdated).then_some((*name, *c))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let errors = all
+        .par_iter()
+        .filter_map(|(name, c)| {
+            (kinds.contains(&c.kind) && c.error.is_some()).then_some((*name, *c))
+        })
+        .collect::<BTreeMap<_, _>>();
 
     let crates = if cli.outdated {
-        let mut crates = outdated.clone();
+        let mut crates = errors.clone();
+        crates.append(&mut outdated.clone());
+        crates.append(&mut outdated_git.clone());
+        crates.append(&mut outdated_local.clone());
         if cli.outdated_rust {
             crates.append(&mut outdated_rust.clone());
         }
         if cli.ignore_req {
             crates.append(&mut outdated_pinned.clone());
         }
+        if cli.yanked {
+            crates.append(&mut outdated_yanked.clone());
+        }
         crates
     } else {
         all.iter().map(|(name, c)| (*name, *c)).collect()
@@ -284,30 +1024,124 @@ fn inner(cli: &List) -> Result<()> {
                 }
             }
 
-            let kinds = if cli.all_kinds {
-                cargo_list::ALL_KINDS.to_vec()
-            } else {
-                cli.kind
-                    .par_iter()
-                    .map(Kind::into)
-                    .collect::<IndexSet<_>>()
-                    .into_iter()
-                    .collect::<Vec<_>>()
-            };
-
             for k in &kinds {
                 println!("{}\n", format!("# {k:?}").magenta().bold());
                 let mut outdated = 0;
                 let mut update_pinned = 0;
                 let mut number = 1;
-                let mut t = if cli.outdated_rust {
-                    Veg::table("#|Name|Pinned|Installed|Available|Rust\n-:|-|-|-|-|-")
-                } else {
-                    Veg::table("#|Name|Pinned|Installed|Available\n-:|-|-|-|-")
+                // Show the Update column if any crate has an update
+                let show_update = *k == cargo_list::Kind::External
+                    && external.values().any(|c| c.update.is_some());
+
+                // Show the Compatible column if the toolchain cannot build an available version
+                let show_compatible = *k == cargo_list::Kind::External
+                    && external.values().any(|c| {
+                        c.error.is_none()
+                            && !c.available.is_empty()
+                            && c.compatible.as_ref() != Some(&c.available)
+                    });
+                // Show the Installer column if updating any crate via cargo binstall
+                let show_installer = cli.update
+                    && (cli.installer == Installer::Binstall
+                        || config
+                            .crates
+                            .values()
+                            .any(|x| x.installer == Some(cargo_list::Installer::Binstall)));
+                let columns = |c: &cargo_list::Crate,
+                               rust: ColoredString,
+                               compatible: ColoredString,
+                               advisory: ColoredString,
+                               update: ColoredString| {
+                    let mut r = vec![];
+                    if show_update {
+                        r.push(update);
+                    }
+                    if show_installer {
+                        r.push(match c.installer {
+                            cargo_list::Installer::Binstall => "binstall".cyan(),
+                            cargo_list::Installer::Cargo => "cargo".normal(),
+                        });
+                    }
+                    if cli.outdated_rust {
+                        r.push(rust);
+                    }
+                    if show_compatible {
+                        r.push(compatible);
+                    }
+                    if audit {
+                        r.push(advisory);
+                    }
+                    r
                 };
+                let mut header = String::from("#|Name|Pinned|Installed|Available");
+                let mut align = String::from("-:|-|-|-|-");
+                if show_update {
+                    header.push_str("|Update");
+                    align.push_str("|-");
+                }
+                if show_installer {
+                    header.push_str("|Installer");
+                    align.push_str("|-");
+                }
+                if cli.outdated_rust {
+                    header.push_str("|Rust");
+                    align.push_str("|-");
+                }
+                if show_compatible {
+                    header.push_str("|Compatible");
+                    align.push_str("|-");
+                }
+                if audit {
+                    header.push_str("|Advisory");
+                    align.push_str("|-");
+                }
+                let mut t = Veg::table(&format!("{header}\n{align}"));
+                let mut incompatible = 0;
+                let mut yanked = 0;
+                let mut vulnerable = 0;
+                let mut failed = vec![];
                 for c in all.values().filter(|x| x.kind == *k) {
-                    if *k == cargo_list::Kind::External {
-                        let (pinned, available) = if let Some(pinned) = &c.version_req {
+                    let advisory = c
+                        .advisories
+                        .iter()
+                        .map(|x| x.id.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let update = match c.update {
+                        Some(cargo_list::Update::Patch) => "patch".green(),
+                        Some(cargo_list::Update::Minor) => "minor".yellow(),
+                        Some(cargo_list::Update::Major) => "major".red(),
+                        Some(cargo_list::Update::Prerelease) => "prerelease".magenta(),
+                        Some(cargo_list::Update::Ahead) => "ahead".cyan(),
+                        None => "".normal(),
+                    };
+                    let advisory = if is_vulnerable(c) {
+                        vulnerable += 1;
+                        advisory.red().bold()
+                    } else {
+                        advisory.yellow()
+                    };
+                    if let Some(error) = &c.error {
+                        t.push(Row::new(
+                            number.to_string().normal(),
+                            c.name.normal(),
+                            c.version_req.as_deref().unwrap_or_default().normal(),
+                            c.installed.yellow(),
+                            "error".red().bold(),
+                            columns(
+                                c,
+                                "".normal(),
+                                "".normal(),
+                                advisory.clone(),
+                                update.clone(),
+                            ),
+                        ));
+                        number += 1;
+                        failed.push((&c.name, error));
+                    } else if *k == cargo_list::Kind::External {
+                        let (pinned, available) = if let Some(hold) = &c.hold {
+                            (hold.clone(), c.available.clone())
+                        } else if let Some(pinned) = &c.version_req {
                             if c.newer.is_empty() {
                                 (String::new(), c.available.clone())
                             } else {
@@ -318,19 +1152,71 @@ fn inner(cli: &List) -> Result<()> {
                             (String::new(), c.available.clone())
                         };
 
+                        let installed = if c.installed_yanked {
+                            if outdated_yanked.contains_key(c.name.as_str()) {
+                                yanked += 1;
+                            }
+                            format!("{} (yanked)", c.installed)
+                        } else {
+                            c.installed.clone()
+                        };
+                        let current = if c.installed_yanked {
+                            installed.red().bold()
+                        } else {
+                            installed.green()
+                        };
+
+                        let compatible = match &c.compatible {
+                            _ if c.available.is_empty() => "".normal(),
+                            Some(compatible) if *compatible == c.available => "".normal(),
+                            Some(compatible) => {
+                                incompatible += 1;
+                                compatible.yellow()
+                            }
+                            None => {
+                                incompatible += 1;
+                                "none".red()
+                            }
+                        };
+
                         if c.outdated {
                             t.push(Row::new(
                                 number.to_string().normal(),
                                 c.name.normal(),
                                 pinned.normal(),
-                                c.installed.red(),
+                                installed.red(),
                                 available.bold(),
-                                if c.outdated_rust {
-                                    c.rust_version.red()
-                                } else {
-                                    c.rust_version.green()
-                                },
-                                cli.outdated_rust,
+                                columns(
+                                    c,
+                                    if c.outdated_rust {
+                                        c.rust_version.red()
+                                    } else {
+                                        c.rust_version.green()
+                                    },
+                                    compatible.clone(),
+                                    advisory.clone(),
+                                    update.clone(),
+                                ),
+                            ));
+                            number += 1;
+                            outdated += 1;
+                        } else if cli.yanked
+                            && c.installed_yanked
+                            && let Some(replacement) = &c.replacement
+                        {
+                            t.push(Row::new(
+                                number.to_string().normal(),
+                                c.name.normal(),
+                                pinned.normal(),
+                                installed.red(),
+                                replacement.bold(),
+                                columns(
+                                    c,
+                                    "".normal(),
+                                    compatible.clone(),
+                                    advisory.clone(),
+                                    update.clone(),
+                                ),
                             ));
                             number += 1;
                             outdated += 1;
@@ -340,10 +1226,15 @@ fn inner(cli: &List) -> Result<()> {
                                     number.to_string().normal(),
                                     c.name.normal(),
                                     pinned.normal(),
-                                    c.installed.green(),
+                                    current.clone(),
                                     "".normal(),
-                                    c.rust_version.red(),
-                                    cli.outdated_rust,
+                                    columns(
+                                        c,
+                                        c.rust_version.red(),
+                                        compatible.clone(),
+                                        advisory.clone(),
+                                        update.clone(),
+                                    ),
                                 ));
                                 number += 1;
                                 outdated += 1;
@@ -352,10 +1243,15 @@ fn inner(cli: &List) -> Result<()> {
                                     number.to_string().normal(),
                                     c.name.normal(),
                                     pinned.normal(),
-                                    c.installed.green(),
+                                    current.clone(),
                                     "".normal(),
-                                    c.rust_version.green(),
-                                    cli.outdated_rust,
+                                    columns(
+                                        c,
+                                        c.rust_version.green(),
+                                        compatible.clone(),
+                                        advisory.clone(),
+                                        update.clone(),
+                                    ),
                                 ));
                                 number += 1;
                             }
@@ -364,10 +1260,15 @@ fn inner(cli: &List) -> Result<()> {
                                 number.to_string().normal(),
                                 c.name.normal(),
                                 pinned.normal(),
-                                c.installed.red(),
+                                installed.red(),
                                 c.newer[0].bold(),
-                                "".normal(),
-                                cli.outdated_rust,
+                                columns(
+                                    c,
+                                    "".normal(),
+                                    compatible.clone(),
+                                    advisory.clone(),
+                                    update.clone(),
+                                ),
                             ));
                             number += 1;
                             outdated += 1;
@@ -376,22 +1277,121 @@ fn inner(cli: &List) -> Result<()> {
                                 number.to_string().normal(),
                                 c.name.normal(),
                                 pinned.normal(),
-                                c.installed.green(),
+                                current.clone(),
                                 "".normal(),
+                                columns(
+                                    c,
+                                    "".normal(),
+                                    compatible.clone(),
+                                    advisory.clone(),
+                                    update.clone(),
+                                ),
+                            ));
+                            number += 1;
+                        }
+                    } else if let Some(git) = &c.git {
+                        let installed = format!(
+                            "{} ({})",
+                            c.installed,
+                            cargo_list::short_commit(&git.commit),
+                        );
+                        let remote = git
+                            .remote_commit
+                            .as_deref()
+                            .map(cargo_list::short_commit)
+                            .unwrap_or_default();
+                        if c.outdated {
+                            t.push(Row::new(
+                                number.to_string().normal(),
+                                c.name.normal(),
                                 "".normal(),
-                                cli.outdated_rust,
+                                installed.red(),
+                                remote.bold(),
+                                columns(
+                                    c,
+                                    "".normal(),
+                                    "".normal(),
+                                    advisory.clone(),
+                                    update.clone(),
+                                ),
+                            ));
+                            number += 1;
+                            outdated += 1;
+                        } else if !cli.outdated {
+                            t.push(Row::new(
+                                number.to_string().normal(),
+                                c.name.normal(),
+                                "".normal(),
+                                if git.remote_commit.is_some() {
+                                    installed.green()
+                                } else {
+                                    installed.cyan()
+                                },
+                                "".normal(),
+                                columns(
+                                    c,
+                                    "".normal(),
+                                    "".normal(),
+                                    advisory.clone(),
+                                    update.clone(),
+                                ),
+                            ));
+                            number += 1;
+                        }
+                    } else if let Some(local) = &c.local {
+                        let available = if local.version == c.installed {
+                            String::from("modified")
+                        } else {
+                            local.version.clone()
+                        };
+                        if c.outdated {
+                            t.push(Row::new(
+                                number.to_string().normal(),
+                                c.name.normal(),
+                                "".normal(),
+                                c.installed.red(),
+                                available.bold(),
+                                columns(
+                                    c,
+                                    "".normal(),
+                                    "".normal(),
+                                    advisory.clone(),
+                                    update.clone(),
+                                ),
+                            ));
+                            number += 1;
+                            outdated += 1;
+                        } else if !cli.outdated {
+                            t.push(Row::new(
+                                number.to_string().normal(),
+                                c.name.normal(),
+                                "".normal(),
+                                c.installed.green(),
+                                "".normal(),
+                                columns(
+                                    c,
+                                    "".normal(),
+                                    "".normal(),
+                                    advisory.clone(),
+                                    update.clone(),
+                                ),
                             ));
                             number += 1;
                         }
-                    } else if !cli.outdated || c.kind == cargo_list::Kind::Git {
+                    } else if !cli.outdated {
                         t.push(Row::new(
                             number.to_string().normal(),
                             c.name.normal(),
                             "".normal(),
                             c.installed.cyan(),
                             "".normal(),
-                            "".normal(),
-                            cli.outdated_rust,
+                            columns(
+                                c,
+                                "".normal(),
+                                "".normal(),
+                                advisory.clone(),
+                                update.clone(),
+                            ),
                         ));
                         number += 1;
                     }
@@ -402,8 +1402,46 @@ fn inner(cli: &List) -> Result<()> {
                     println!("{}", t.markdown()?);
                 }
 
+                // Print the errors
+                for (name, error) in &failed {
+                    println!("{}\n", format!("*Failed to check `{name}`: {error}*").red());
+                }
+
                 // Print a summary
-                if *k == cargo_list::Kind::External {
+                if *k == cargo_list::Kind::Local {
+                    if outdated == 0 {
+                        println!(
+                            "{}\n",
+                            "**All local crates are up-to-date!**".green().bold()
+                        );
+                    } else {
+                        println!(
+                            "{}\n",
+                            format!(
+                                "**Need to update {} local crate{}!**",
+                                outdated,
+                                if outdated == 1 { "" } else { "s" }
+                            )
+                            .red()
+                            .bold(),
+                        );
+                    }
+                } else if *k == cargo_list::Kind::Git && !cli.offline {
+                    if outdated == 0 {
+                        println!("{}\n", "**All git crates are up-to-date!**".green().bold());
+                    } else {
+                        println!(
+                            "{}\n",
+                            format!(
+                                "**Need to update {} git crate{}!**",
+                                outdated,
+                                if outdated == 1 { "" } else { "s" }
+                            )
+                            .red()
+                            .bold(),
+                        );
+                    }
+                } else if *k == cargo_list::Kind::External {
                     if outdated == 0 {
                         println!(
                             "{}\n",
@@ -434,91 +1472,189 @@ fn inner(cli: &List) -> Result<()> {
                             .italic(),
                         );
                     }
+
+                    if incompatible > 0 {
+                        println!(
+                            "{}\n",
+                            format!(
+                                "*The active toolchain cannot build {} version of {} external \
+                                crate{}{}.*",
+                                if cli.msrv {
+                                    "any available"
+                                } else {
+                                    "the available"
+                                },
+                                incompatible,
+                                if incompatible == 1 { "" } else { "s" },
+                                if cli.msrv { "" } else { "; consider `--msrv`" },
+                            )
+                            .yellow()
+                            .italic(),
+                        );
+                    }
+
+                    if audit {
+                        if vulnerable == 0 {
+                            println!(
+                                "{}\n",
+                                "**No known vulnerabilities in external crates!**"
+                                    .green()
+                                    .bold(),
+                            );
+                        } else {
+                            println!(
+                                "{}\n",
+                                format!(
+                                    "**Found known vulnerabilities in {} external crate{}!**",
+                                    vulnerable,
+                                    if vulnerable == 1 { "" } else { "s" },
+                                )
+                                .red()
+                                .bold(),
+                            );
+                        }
+                    }
+
+                    if !cli.yanked && yanked > 0 {
+                        println!(
+                            "{}\n",
+                            format!(
+                                "*The installed version of {} external crate{} was yanked; \
+                                consider updating via `--yanked`.*",
+                                yanked,
+                                if yanked == 1 { "" } else { "s" },
+                            )
+                            .red()
+                            .italic(),
+                        );
+                    }
+
+                    if external.values().any(|c| c.stale) {
+                        println!(
+                            "{}\n",
+                            "*Available versions are from the local registry index cache and may \
+                            be stale.*"
+                                .yellow()
+                                .italic(),
+                        );
+                    }
                 }
             }
 
             if cli.update {
-                // Update external crates
+                let mut updates = BTreeMap::new();
+                let mut update_pinned = 0;
                 if kinds.contains(&cargo_list::Kind::External) {
-                    let mut updates = outdated.clone();
+                    updates.append(&mut outdated.clone());
                     if cli.outdated_rust {
                         updates.append(&mut outdated_rust.clone());
                     }
-                    let mut update_pinned = 0;
                     if cli.ignore_req {
                         updates.append(&mut outdated_pinned.clone());
                         update_pinned += outdated_pinned.len();
                     }
-                    if !updates.is_empty() {
-                        println!("{}\n", "# External".magenta().bold());
-                        let mut shell = Shell {
-                            dry_run: cli.dry_run,
-                            ..Default::default()
-                        };
-                        if cli.dry_run {
-                            shell.info = String::from("bash");
-                        }
-                        for (name, c) in &updates {
-                            println!("{}\n", format!("## {name:?}").yellow().bold());
-                            let _ = shell.run(&[Command {
-                                command: c
-                                    .update_command(
-                                        cli.ignore_req && outdated_pinned.contains_key(name),
-                                    )
-                                    .join(" "),
-                                ..Default::default()
-                            }]);
-                        }
+                    if cli.yanked {
+                        updates.append(&mut outdated_yanked.clone());
+                    }
+                }
 
-                        // Print summary
-                        let mut c = cli.clone();
-                        c.update = false;
-                        c.outdated = false;
-                        c.include = updates.keys().map(ToString::to_string).collect();
-                        inner(&c)?;
-                        if !cli.ignore_req && update_pinned > 0 {
-                            println!(
-                                "{}\n",
-                                format!(
-                                    "*Consider updating {} pinned external crate{} via `-I`.*",
-                                    update_pinned,
-                                    if update_pinned == 1 { "" } else { "s" }
-                                )
-                                .yellow()
-                                .italic(),
-                            );
-                        }
+                // Save a snapshot of the crates to update so they can be rolled back (local crates
+                // cannot be: their previous source is gone)
+                if !cli.dry_run {
+                    let mut names = updates.keys().copied().collect::<Vec<_>>();
+                    if kinds.contains(&cargo_list::Kind::Git) {
+                        names.extend(outdated_git.keys());
                     }
+                    save_snapshot(&installed, &names)?;
                 }
 
-                // Update git crates
-                if kinds.contains(&cargo_list::Kind::Git) {
-                    let outdated = all
-                        .iter()
-                        .filter(|(_name, c)| c.kind == cargo_list::Kind::Git)
-                        .collect::<BTreeMap<_, _>>();
-                    if !outdated.is_empty() {
-                        println!("{}\n", "# Git".magenta().bold());
-                        let mut shell = Shell {
-                            dry_run: cli.dry_run,
-                            ..Default::default()
+                let external_jobs = updates
+                    .values()
+                    .map(|c| {
+                        update_job(
+                            c,
+                            cli.ignore_req && outdated_pinned.contains_key(c.name.as_str()),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                let jobs = |kind, outdated: &BTreeMap<&str, &cargo_list::Crate>| {
+                    if kinds.contains(&kind) {
+                        outdated.values().map(|c| update_job(c, false)).collect()
+                    } else {
+                        vec![]
+                    }
+                };
+                let git_jobs = jobs(cargo_list::Kind::Git, &outdated_git);
+                let local_jobs = jobs(cargo_list::Kind::Local, &outdated_local);
+
+                let runner = Runner {
+                    jobs: cli.jobs,
+                    fail_fast: cli.fail_fast,
+                    log_dir: if cli.jobs > 1 && !cli.dry_run {
+                        Some(
+                            log_dir()
+                                .ok_or_else(|| anyhow::anyhow!("Failed to get the data directory"))?
+                                .join(
+                                    std::time::SystemTime::now()
+                                        .duration_since(std::time::UNIX_EPOCH)?
+                                        .as_millis()
+                                        .to_string(),
+                                ),
+                        )
+                    } else {
+                        None
+                    },
+                    target_dir: cli.target_dir.as_deref().map(expanduser),
+                };
+                let mut results = vec![];
+                if let Some(dir) = &runner.log_dir {
+                    // Update all crates concurrently
+                    let jobs = [external_jobs, git_jobs, local_jobs].concat();
+                    if !jobs.is_empty() {
+                        eprintln!(
+                            "Updating {} crate{} ({} at a time); logs are in `{}`\n",
+                            jobs.len(),
+                            if jobs.len() == 1 { "" } else { "s" },
+                            cli.jobs,
+                            dir.display(),
+                        );
+                        runner.run(&jobs, &mut results, |x| show_progress(&x, true))?;
+                    }
+                    if !updates.is_empty() {
+                        print_update_summary(cli, config, &updates, update_pinned)?;
+                    }
+                } else {
+                    for jobs in [&external_jobs, &git_jobs, &local_jobs] {
+                        let Some(kind) = jobs.first().map(|x| &x.kind) else {
+                            continue;
                         };
+                        println!("{}\n", format!("# {kind:?}").magenta().bold());
                         if cli.dry_run {
-                            shell.info = String::from("bash");
+                            for job in jobs {
+                                println!("{}\n", format!("## {:?}", job.name).yellow().bold());
+                                let shell = Shell {
+                                    dry_run: true,
+                                    info: String::from("bash"),
+                                    ..Default::default()
+                                };
+                                let _ = shell.run(&[Command {
+                                    command: job.command.join(" "),
+                                    ..Default::default()
+                                }]);
+                            }
+                        } else {
+                            runner.run(jobs, &mut results, |x| show_progress(&x, false))?;
                         }
-                        for (name, c) in &outdated {
-                            println!("{}\n", format!("## {name:?}").yellow().bold());
-                            let _ = shell.run(&[Command {
-                                command: c
-                                    .update_command(
-                                        cli.ignore_req && outdated_pinned.contains_key(*name),
-                                    )
-                                    .join(" "),
-                                ..Default::default()
-                            }]);
+                        if *kind == cargo_list::Kind::External {
+                            print_update_summary(cli, config, &updates, update_pinned)?;
                         }
                     }
                 }
+
+                if !results.is_empty() {
+                    print_results(&results, "Updated", "update")?;
+                    Runner::check(&results)?;
+                }
             }
         }
         Json => {
@@ -550,6 +1686,174 @@ fn inner(cli: &List) -> Result<()> {
     Ok(())
 }
 
+/// Re-list the updated external crates
+fn print_update_summary(
+    cli: &List,
+    config: &Config,
+    updates: &BTreeMap<&str, &cargo_list::Crate>,
+    update_pinned: usize,
+) -> Result<()> {
+    let mut c = cli.clone();
+    c.update = false;
+    c.outdated = false;
+    c.vulnerable = false;
+    c.only.clear();
+    c.include = updates.keys().map(ToString::to_string).collect();
+    inner(&c, config)?;
+    if !cli.ignore_req && update_pinned > 0 {
+        println!(
+            "{}\n",
+            format!(
+                "*Consider updating {} pinned external crate{} via `-I`.*",
+                update_pinned,
+                if update_pinned == 1 { "" } else { "s" }
+            )
+            .yellow()
+            .italic(),
+        );
+    }
+    Ok(())
+}
+
+/// Job to update a crate
+fn update_job(c: &cargo_list::Crate, pinned: bool) -> Job {
+    Job {
+        name: c.name.clone(),
+        kind: c.kind.clone(),
+        command: c.update_command(pinned),
+    }
+}
+
+/**
+Show the progress of a [`Runner`]
+
+Logged jobs run concurrently, so only a line per finished job is shown; otherwise, each job is shown
+with its output in a fenced block.
+*/
+fn show_progress(event: &Event, logged: bool) {
+    match event {
+        Event::Start(_) if logged => {}
+        Event::Start(job) => {
+            println!("{}\n", format!("## {:?}", job.name).yellow().bold());
+            println!(
+                "{}\n{}{}",
+                "```text".bright_black(),
+                "$ ".bright_black(),
+                job.command.join(" ").cyan().bold(),
+            );
+        }
+        Event::Finish(result) if logged => {
+            eprintln!(
+                "{} `{}`{}",
+                match result.status {
+                    Status::Ok => "Finished",
+                    Status::Failed => "Failed",
+                    Status::Skipped => "Skipped",
+                },
+                result.name,
+                if result.status == Status::Skipped {
+                    String::from(" after a failure")
+                } else {
+                    format!(" in {}", format_duration(result.duration))
+                },
+            );
+        }
+        Event::Finish(result) if result.status == Status::Skipped => {
+            println!("{}\n", format!("## {:?}", result.name).yellow().bold());
+            println!("{}\n", "*Skipped after a failure.*".yellow().italic());
+        }
+        Event::Finish(_) => println!("{}\n", "```".bright_black()),
+    }
+}
+
+/**
+Print a table of the results of the jobs, the output tails of failed ones, and a summary (for
+example, "Updated" and "update")
+*/
+fn print_results(results: &[JobResult], done: &str, action: &str) -> Result<()> {
+    println!("{}\n", "# Results".magenta().bold());
+    let logged = results.iter().any(|x| x.log.is_some());
+    let mut t = Veg::table(if logged {
+        "#|Name|Kind|Result|Duration|Log\n-:|-|-|-|-:|-"
+    } else {
+        "#|Name|Kind|Result|Duration\n-:|-|-|-|-:"
+    });
+    for (i, x) in results.iter().enumerate() {
+        t.push(Row::new(
+            (i + 1).to_string().normal(),
+            x.name.normal(),
+            format!("{:?}", x.kind).normal(),
+            match x.status {
+                Status::Ok => "ok".green(),
+                Status::Failed => "failed".red().bold(),
+                Status::Skipped => "skipped".yellow(),
+            },
+            format_duration(x.duration).normal(),
+            x.log
+                .iter()
+                .map(|x| x.display().to_string().normal())
+                .collect(),
+        ));
+    }
+    println!("{}", t.markdown()?);
+
+    for x in results.iter().filter(|x| x.failed()) {
+        println!("{}\n", format!("## {:?}", x.name).red().bold());
+        println!("```text\n{}\n```\n", x.tail.join("\n"));
+    }
+    let failed = results.iter().filter(|x| x.failed()).count();
+    if failed == 0 {
+        let updated = results.iter().filter(|x| x.status == Status::Ok).count();
+        println!(
+            "{}\n",
+            format!(
+                "**{done} {updated} crate{}!**",
+                if updated == 1 { "" } else { "s" }
+            )
+            .green()
+            .bold()
+        );
+    } else {
+        println!(
+            "{}\n",
+            format!(
+                "**Failed to {action} {failed} crate{}!**",
+                if failed == 1 { "" } else { "s" }
+            )
+            .red()
+            .bold()
+        );
+    }
+    Ok(())
+}
+
+/// Format a duration in seconds
+fn format_duration(duration: Duration) -> String {
+    format!("{:.1}s", duration.as_secs_f64())
+}
+
+/// Save a snapshot of the given crates before updating them
+fn save_snapshot(installed: &Crates, names: &[&str]) -> Result<()> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    let Some(dir) = snapshot_dir() else {
+        eprintln!("Skipped saving a snapshot: no data directory");
+        return Ok(());
+    };
+    let mut manifest = Manifest::from(installed);
+    manifest
+        .crates
+        .retain(|name, _| names.contains(&name.as_str()));
+    manifest.save_snapshot(&dir)?;
+    Ok(())
+}
+
+/// Return true if any security advisory (not just informational) affects the installed version
+fn is_vulnerable(c: &cargo_list::Crate) -> bool {
+    c.advisories.iter().any(|x| x.informational.is_none())
+}
+
 fn get_config_path(config: &str) -> std::path::PathBuf {
     if let Some(s) = config.strip_prefix("$CARGO_HOME/") {
         // Default
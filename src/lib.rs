@@ -7,14 +7,17 @@ use {
     dirs::home_dir,
     rayon::prelude::*,
     regex::RegexSet,
-    reqwest::blocking::Client,
+    reqwest::{
+        StatusCode,
+        blocking::{Client, RequestBuilder, Response},
+    },
     serde::{Deserialize, Serialize},
-    sprint::{Command, Pipe, Shell},
     std::{
         collections::BTreeMap,
         fs::File,
         path::{Path, PathBuf},
-        sync::LazyLock,
+        sync::{LazyLock, Mutex, PoisonError},
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
     },
 };
 
@@ -46,8 +49,128 @@ static CLIENT: LazyLock<Client> = LazyLock::new(|| {
 
 //--------------------------------------------------------------------------------------------------
 
+/// Error returned by the library API
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Cargo install metadata file (or configuration file or manifest) does not exist
+    #[error("File `{}` does not exist", path.display())]
+    NotFound { path: PathBuf },
+
+    /// Not able to read the cargo install metadata file (or configuration file or manifest)
+    #[error("Failed to read `{}`", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    /// Not able to parse the cargo install metadata file (or JSON manifest)
+    #[error("Failed to parse `{}`", path.display())]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    /// Entry in the cargo install metadata file is malformed
+    #[error("Malformed entry '{key}': {reason}")]
+    MalformedEntry { key: String, reason: String },
+
+    /// Crate name contains characters other than ASCII alphanumerics, `-`, and `_`
+    #[error("Invalid crate name `{name}`")]
+    InvalidName { name: String },
+
+    /// Not able to parse the configuration file
+    #[error("Failed to parse `{}`", path.display())]
+    InvalidConfig {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    /// Not able to parse the TOML manifest file
+    #[error("Failed to parse `{}`", path.display())]
+    InvalidManifest {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    /// Manifest file was written in a newer format
+    #[error(
+        "Manifest `{}` has unsupported version {version} (expected up to {MANIFEST_VERSION})",
+        path.display(),
+    )]
+    UnsupportedManifest { path: PathBuf, version: u32 },
+
+    /// Include pattern is not a valid regular expression
+    #[error("Invalid pattern")]
+    InvalidPattern(#[from] regex::Error),
+
+    /// Not able to get the versions of a crate from its registry (unreachable, timed out, not
+    /// found...)
+    #[error("Failed to get the versions of `{name}` from the registry")]
+    Registry { name: String, source: anyhow::Error },
+
+    /// Version requirement is not valid
+    #[error("Invalid version requirement `{requirement}` for `{name}`")]
+    InvalidRequirement {
+        name: String,
+        requirement: String,
+        source: semver::Error,
+    },
+
+    /// No available version matches the version requirement (or no version is available at all)
+    #[error(
+        "Failed to find an available version of `{name}`{}",
+        requirement.as_ref().map(|x| format!(" matching the requirement `{x}`")).unwrap_or_default()
+    )]
+    NoMatchingVersion {
+        name: String,
+        requirement: Option<String>,
+    },
+
+    /// No snapshot to roll back to (containing the crate, if given)
+    #[error(
+        "Failed to find a snapshot{}",
+        name.as_ref().map(|x| format!(" of `{x}`")).unwrap_or_default()
+    )]
+    NoSnapshot { name: Option<String> },
+
+    /// Commands of one or more crates failed (see [`Runner::check`])
+    #[error("Failed to run the commands of {failed} crate{}", if *failed == 1 { "" } else { "s" })]
+    Failed { failed: usize },
+
+    /// Crate failed to process
+    #[error("Failed to process crate '{key}'")]
+    Crate { key: String, source: Box<Error> },
+
+    /// Multiple crates failed to process (strict mode)
+    #[error(
+        "Errors: {}",
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    )]
+    Crates(Vec<Error>),
+
+    /// Any other error
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Format an error with its sources like `{:#}` does for an [`anyhow::Error`]
+fn error_chain(e: &dyn std::error::Error) -> String {
+    let mut r = e.to_string();
+    let mut source = e.source();
+    while let Some(e) = source {
+        r.push_str(": ");
+        r.push_str(&e.to_string());
+        source = e.source();
+    }
+    r
+}
+
+//--------------------------------------------------------------------------------------------------
+
 /// Crate kind
-#[derive(Debug, Default, Serialize, Eq, PartialEq, Hash, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Eq, PartialEq, Hash, Clone)]
+#[serde(rename_all(deserialize = "lowercase"))]
 pub enum Kind {
     Local,
     Git,
@@ -75,316 +198,2839 @@ impl Kind {
 
 //--------------------------------------------------------------------------------------------------
 
-/// All installed crates
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Crates {
-    installs: BTreeMap<String, Crate>,
+/// Class of the update from the installed version to the available version
+#[derive(Debug, Serialize, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum Update {
+    /// Compatible update within the same minor version (`1.2.3` to `1.2.4`, `0.2.3` to `0.2.4`)
+    Patch,
 
-    #[serde(skip)]
-    pub active_toolchain: String,
+    /// Compatible update to a new minor version (`1.2.3` to `1.3.0`)
+    Minor,
 
-    #[serde(skip)]
-    pub active_version: String,
+    /// Incompatible update per cargo's semver compatibility rules (`1.2.3` to `2.0.0`, `0.2.3` to
+    /// `0.3.0`, `0.0.2` to `0.0.3`)
+    Major,
+
+    /// Update to a prerelease version
+    Prerelease,
+
+    /// Installed version is newer than the available version
+    Ahead,
 }
 
-impl Crates {
-    /**
-    Deserialize from a `~/.cargo/.crates2.json` file and process each crate in
-    parallel to:
+impl Update {
+    /// Classify the update from the installed version to the available version (`None` if equal)
+    #[must_use]
+    pub fn classify(installed: &semver::Version, available: &semver::Version) -> Option<Update> {
+        if installed == available {
+            None
+        } else if installed > available {
+            Some(Update::Ahead)
+        } else if !available.pre.is_empty() {
+            Some(Update::Prerelease)
+        } else if !semver::VersionReq::parse(&format!("^{installed}"))
+            .is_ok_and(|req| req.matches(available))
+        {
+            Some(Update::Major)
+        } else if (installed.major, installed.minor) == (available.major, available.minor) {
+            Some(Update::Patch)
+        } else {
+            Some(Update::Minor)
+        }
+    }
+}
 
-    * Parse the name, version, source, rust version
-    * Get the latest avaiable version
-    * Determine the crate type
+//--------------------------------------------------------------------------------------------------
 
-    # Errors
+/// Git reference tracked by a crate installed via git
+#[derive(Debug, Serialize, Eq, PartialEq, Clone)]
+pub enum GitReference {
+    DefaultBranch,
+    Branch(String),
+    Tag(String),
+    Rev(String),
+}
 
-    Returns an error if not able to read the file at the given path
-    */
-    pub fn from(path: &Path) -> Result<Crates> {
-        Crates::from_include(path, &[])
-    }
+/// Git repository, tracked reference, and commits of a crate installed via git
+#[derive(Debug, Serialize, Eq, PartialEq, Clone)]
+pub struct GitSource {
+    /// Repository URL
+    pub url: String,
 
-    /**
-    Return true if no crates are installed
-    */
-    #[must_use]
-    pub fn is_empty(&self) -> bool {
-        self.installs.is_empty()
-    }
+    /// Tracked reference
+    pub reference: GitReference,
 
-    /// Return a view of all crates
-    #[must_use]
-    pub fn crates(&self) -> BTreeMap<&str, &Crate> {
-        self.installs
-            .values()
-            .map(|x| (x.name.as_str(), x))
-            .collect()
+    /// Installed commit
+    pub commit: String,
+
+    /// Commit at the tip of the tracked reference, if checked
+    pub remote_commit: Option<String>,
+}
+
+impl GitSource {
+    /// Parse a `git+URL[?branch=B|?tag=T|?rev=R]#COMMIT` source
+    fn from(source: &str) -> Option<GitSource> {
+        let (url, commit) = source.strip_prefix("git+")?.split_once('#')?;
+        let (url, reference) = match url.split_once('?') {
+            Some((url, query)) => (
+                url,
+                match query.split_once('=')? {
+                    ("branch", x) => GitReference::Branch(x.to_string()),
+                    ("tag", x) => GitReference::Tag(x.to_string()),
+                    ("rev", x) => GitReference::Rev(x.to_string()),
+                    _ => GitReference::DefaultBranch,
+                },
+            ),
+            None => (url, GitReference::DefaultBranch),
+        };
+        Some(GitSource {
+            url: url.to_string(),
+            reference,
+            commit: commit.to_string(),
+            remote_commit: None,
+        })
     }
 
     /**
-    Like the [`Crates::from`] method, but accepts zero or more include patterns to match against
-    crate names
+    Get the commit at the tip of the tracked reference via `git ls-remote`; a `rev` is pinned so
+    it is the installed commit
 
     # Errors
 
-    Returns an error if not able to read the file at the given path or a pattern is not a valid
-    regular expression
+    Returns an error if `git ls-remote` fails or does not find the reference
     */
-    #[allow(clippy::missing_panics_doc)]
-    pub fn from_include(path: &Path, patterns: &[&str]) -> Result<Crates> {
-        let mut crates: Crates = serde_json::from_reader(File::open(path)?)?;
-        if !patterns.is_empty() {
-            let set = RegexSet::new(patterns)?;
-            crates.installs = crates
-                .installs
-                .into_par_iter()
-                .filter_map(|(k, v)| {
-                    if set.is_match(k.split_once(' ').unwrap().0) {
-                        Some((k, v))
-                    } else {
-                        None
-                    }
-                })
-                .collect();
+    fn fetch_remote_commit(&self) -> Result<String> {
+        let refs = match &self.reference {
+            GitReference::Rev(_) => return Ok(self.commit.clone()),
+            GitReference::DefaultBranch => vec![String::from("HEAD")],
+            GitReference::Branch(branch) => vec![format!("refs/heads/{branch}")],
+            GitReference::Tag(tag) => {
+                vec![format!("refs/tags/{tag}^{{}}"), format!("refs/tags/{tag}")]
+            }
+        };
+        let output = std::process::Command::new("git")
+            .arg("ls-remote")
+            .arg(&self.url)
+            .args(&refs)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .context("Failed to run `git ls-remote`")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "`git ls-remote {}` failed: {}",
+                self.url,
+                String::from_utf8_lossy(&output.stderr).trim(),
+            ));
         }
-        crates.active_toolchain = active_toolchain();
-        crates.active_version = crates
-            .active_toolchain
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let commits = stdout
             .lines()
             .filter_map(|line| {
-                line.split(' ')
-                    .skip_while(|&word| word != "rustc")
-                    .nth(1)
-                    .map(ToString::to_string)
-            })
-            .nth(0)
-            .unwrap();
-        let errors = crates
-            .installs
-            .par_iter_mut()
-            .filter_map(|(k, v)| {
-                v.init(k, &crates.active_version)
-                    .with_context(|| format!("Failed to process crate '{k}'"))
-                    .err()
+                let (commit, name) = line.split_once('\t')?;
+                Some((name, commit))
             })
-            .collect::<Vec<_>>();
-        if errors.is_empty() {
-            Ok(crates)
-        } else {
-            Err(anyhow!(format!(
-                "Errors: {}",
-                errors
-                    .iter()
-                    .map(ToString::to_string)
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            )))
-        }
+            .collect::<BTreeMap<_, _>>();
+
+        // Prefer the peeled commit of an annotated tag
+        refs.iter()
+            .find_map(|x| commits.get(x.as_str()).map(|commit| (*commit).to_string()))
+            .ok_or_else(|| anyhow!("Failed to find `{}` in `{}`", refs[0], self.url))
+    }
+
+    /// Return true if the tip of the tracked reference is a different commit than installed
+    #[must_use]
+    pub fn is_outdated(&self) -> bool {
+        self.remote_commit
+            .as_ref()
+            .is_some_and(|remote| *remote != self.commit)
     }
 }
 
 //--------------------------------------------------------------------------------------------------
 
-/// Individual installed crate
-#[derive(Debug, Serialize, Deserialize)]
-#[allow(clippy::struct_excessive_bools)]
-pub struct Crate {
-    #[serde(skip_deserializing)]
-    pub name: String,
+/// Source directory and manifest version of a crate installed via `cargo install --path`
+#[derive(Debug, Serialize, Eq, PartialEq, Clone)]
+pub struct LocalSource {
+    /// Source directory
+    pub path: PathBuf,
 
-    #[serde(skip_deserializing)]
-    pub kind: Kind,
+    /// Version in the source directory's `Cargo.toml`
+    pub version: String,
 
-    #[serde(skip_deserializing)]
-    pub installed: String,
+    /// Source files were modified after the crate was installed (if checked)
+    pub modified: bool,
+}
 
-    #[serde(skip_deserializing)]
-    installed_: Option<semver::Version>,
+impl LocalSource {
+    /**
+    Read the version from the `Cargo.toml` in the directory of a `path+file://` source; inherit it
+    from `[workspace.package]` if needed
 
-    #[serde(skip_deserializing)]
-    prerelease: bool,
+    # Errors
 
-    #[serde(skip_deserializing)]
-    pub available: String,
+    Returns an error if the source is not a valid file URL or not able to read or parse the
+    manifest
+    */
+    fn from(source: &str) -> Result<LocalSource> {
+        let url = source
+            .strip_prefix("path+")
+            .ok_or_else(|| anyhow!("Invalid path source `{source}`"))?;
+        let path = reqwest::Url::parse(url)?
+            .to_file_path()
+            .map_err(|()| anyhow!("Invalid path source `{source}`"))?;
+        let manifest = read_manifest(&path.join("Cargo.toml"))?;
+        let version = match manifest.get("package").and_then(|x| x.get("version")) {
+            Some(toml::Value::String(version)) => version.clone(),
+            Some(toml::Value::Table(x))
+                if x.get("workspace") == Some(&toml::Value::Boolean(true)) =>
+            {
+                path.ancestors()
+                    .skip(1)
+                    .filter_map(|dir| read_manifest(&dir.join("Cargo.toml")).ok())
+                    .find_map(|x| {
+                        x.get("workspace")?
+                            .get("package")?
+                            .get("version")?
+                            .as_str()
+                            .map(ToString::to_string)
+                    })
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Failed to find the workspace version for `{}`",
+                            path.display()
+                        )
+                    })?
+            }
+            // Cargo defaults to `0.0.0` if the version is omitted
+            _ => String::from("0.0.0"),
+        };
+        Ok(LocalSource {
+            path,
+            version,
+            modified: false,
+        })
+    }
+}
 
-    #[serde(skip_deserializing)]
-    pub newer: Vec<String>,
+/// Read and parse a `Cargo.toml` file
+fn read_manifest(path: &Path) -> Result<toml::Table> {
+    toml::from_str(
+        &std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read `{}`", path.display()))?,
+    )
+    .with_context(|| format!("Failed to parse `{}`", path.display()))
+}
 
-    #[serde(skip_deserializing)]
-    pub rust_version: String,
+/**
+Get the newest modification time of the files in a source directory, skipping hidden entries and
+`target` directories
+*/
+fn newest_mtime(dir: &Path) -> Option<SystemTime> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            !name.starts_with('.') && name != "target"
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            if metadata.is_dir() {
+                newest_mtime(&entry.path())
+            } else {
+                metadata.modified().ok()
+            }
+        })
+        .max()
+}
 
-    #[serde(skip_deserializing)]
-    pub outdated: bool,
+//--------------------------------------------------------------------------------------------------
 
-    #[serde(skip_deserializing)]
-    pub outdated_rust: bool,
+/// Security advisory from the [RustSec advisory database](https://github.com/rustsec/advisory-db)
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct Advisory {
+    /// Identifier (for example, `RUSTSEC-2021-0001`)
+    pub id: String,
 
-    #[serde(skip_deserializing)]
-    source: String,
+    /// Title from the advisory's description
+    #[serde(default)]
+    pub title: String,
 
-    pub version_req: Option<String>,
-    bins: Vec<String>,
-    features: Vec<String>,
-    all_features: bool,
-    no_default_features: bool,
-    profile: String,
-    target: String,
-    rustc: String,
-}
+    /// Date the advisory was reported
+    pub date: String,
 
-impl Crate {
-    /// Initialize additional fields after deserialization
-    fn init(&mut self, k: &str, active_version: &str) -> Result<()> {
-        let mut s = k.split(' ');
-        self.name = s.next().unwrap().to_string();
-        self.installed = s.next().unwrap().to_string();
-        self.source = s
-            .next()
-            .unwrap()
-            .strip_prefix('(')
-            .unwrap()
-            .strip_suffix(')')
-            .unwrap()
-            .to_string();
-        self.installed_ = semver::Version::parse(&self.installed).ok();
-        self.prerelease = self.installed_.as_ref().is_some_and(|x| !x.pre.is_empty());
+    /// URL with more information
+    #[serde(default)]
+    pub url: Option<String>,
 
-        self.kind = Kind::from(&self.source);
+    /// Kind of informational advisory (`unmaintained`, `unsound`...) (`None` if a vulnerability)
+    #[serde(default)]
+    pub informational: Option<String>,
 
-        self.rust_version = self
-            .rustc
-            .strip_prefix("rustc ")
-            .unwrap()
-            .split_once(' ')
-            .unwrap()
-            .0
-            .to_string();
+    /// Other identifiers (for example, CVE IDs)
+    #[serde(default)]
+    pub aliases: Vec<String>,
 
-        self.outdated_rust = self.rust_version != active_version;
+    /// Version requirements of the patched versions
+    #[serde(default)]
+    pub patched: Vec<String>,
+}
 
-        if self.kind == External {
-            (self.available, self.newer) = latest(&self.name, &self.version_req, self.prerelease)?;
-            self.outdated = self.installed != self.available;
-        }
+/// Advisory file front matter
+#[derive(Deserialize)]
+struct AdvisoryFile {
+    advisory: AdvisoryFields,
 
-        Ok(())
-    }
+    #[serde(default)]
+    versions: AdvisoryVersions,
+}
 
-    /**
-    Generate the cargo install command to update the crate
+#[derive(Deserialize)]
+struct AdvisoryFields {
+    id: String,
+    date: String,
 
-    # Panics
+    #[serde(default)]
+    url: Option<String>,
 
-    Panics if a git crate and not able to find `#` in the source
-    */
-    pub fn update_command(&self, pinned: bool) -> Vec<String> {
-        let mut r = vec!["cargo", "install"];
+    #[serde(default)]
+    informational: Option<String>,
 
-        if self.no_default_features {
-            r.push("--no-default-features");
-        }
+    #[serde(default)]
+    aliases: Vec<String>,
 
-        let features = if self.features.is_empty() {
-            None
-        } else {
-            Some(self.features.join(","))
-        };
-        if let Some(features) = &features {
-            r.push("-F");
-            r.push(features);
-        }
+    #[serde(default)]
+    withdrawn: Option<String>,
+}
 
-        if !pinned && let Some(version) = &self.version_req {
-            r.push("--version");
-            r.push(version);
-        }
+#[derive(Default, Deserialize)]
+struct AdvisoryVersions {
+    #[serde(default)]
+    patched: Vec<String>,
 
-        r.push("--profile");
-        r.push(&self.profile);
+    #[serde(default)]
+    unaffected: Vec<String>,
+}
 
-        r.push("--target");
-        r.push(&self.target);
+impl Advisory {
+    /**
+    Load the advisories affecting the given version of a crate from a local clone of the advisory
+    database, skipping withdrawn ones
 
-        if self.outdated_rust {
-            r.push("--force");
-        }
+    # Errors
 
-        if self.kind == Git {
-            r.push("--git");
-            r.push(&self.source[4..self.source.find('#').unwrap()]);
-            for bin in &self.bins {
-                r.push(bin);
+    Returns an error if not able to read or parse an advisory file
+    */
+    fn load(db: &Path, name: &str, version: &semver::Version) -> Result<Vec<Advisory>> {
+        let dir = db.join("crates").join(name);
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+        let mut r = vec![];
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|x| x != "md") {
+                continue;
+            }
+            let text = std::fs::read_to_string(&path)?;
+            if let Some(advisory) = Advisory::parse(&text, version)
+                .with_context(|| format!("Failed to parse `{}`", path.display()))?
+            {
+                r.push(advisory);
             }
-        } else {
-            r.push(&self.name);
         }
+        r.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(r)
+    }
 
-        r.into_iter().map(String::from).collect()
+    /**
+    Parse an advisory (TOML front matter in a fenced code block followed by a Markdown
+    description) and return it if it affects the given version
+    */
+    fn parse(text: &str, version: &semver::Version) -> Result<Option<Advisory>> {
+        let (front, description) = text
+            .trim_start()
+            .strip_prefix("```toml")
+            .and_then(|x| x.split_once("\n```"))
+            .ok_or_else(|| anyhow!("Missing the TOML front matter"))?;
+        let file: AdvisoryFile = toml::from_str(front)?;
+        let matches = |reqs: &[String]| {
+            reqs.iter().any(|x| {
+                semver::VersionReq::parse(x)
+                    .ok()
+                    .is_some_and(|req| req.matches(version))
+            })
+        };
+        if file.advisory.withdrawn.is_some()
+            || matches(&file.versions.patched)
+            || matches(&file.versions.unaffected)
+        {
+            return Ok(None);
+        }
+        Ok(Some(Advisory {
+            id: file.advisory.id,
+            title: description
+                .lines()
+                .find_map(|line| line.strip_prefix("# "))
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+            date: file.advisory.date,
+            url: file.advisory.url,
+            informational: file.advisory.informational,
+            aliases: file.advisory.aliases,
+            patched: file.versions.patched,
+        }))
     }
 }
 
 //--------------------------------------------------------------------------------------------------
 
-/**
-Deserialize the crate version object returned via the crates.io API
-(`https://crates.io/api/v1/crates/{name}/versions`) in the [`latest()`] function
-*/
-#[derive(Debug, Deserialize)]
-struct Versions {
-    versions: Vec<Version>,
+/// Abbreviate a commit hash to its first 7 characters
+#[must_use]
+pub fn short_commit(commit: &str) -> &str {
+    commit
+        .char_indices()
+        .nth(7)
+        .map_or(commit, |(i, _c)| &commit[..i])
 }
 
-impl Versions {
-    fn iter(&self) -> std::slice::Iter<'_, Version> {
-        self.versions.iter()
-    }
+//--------------------------------------------------------------------------------------------------
 
-    fn available(&self, prerelease: bool) -> Vec<&Version> {
-        self.iter().filter(|x| x.is_available(prerelease)).collect()
-    }
-}
+/// Index URL of the crates.io registry as recorded for crates installed via its git index
+const CRATES_IO_INDEX: &str = "https://github.com/rust-lang/crates.io-index";
 
-#[derive(Debug, Deserialize)]
-struct Version {
-    num: semver::Version,
-    yanked: bool,
-}
+/// Index URL of the crates.io registry as recorded for crates installed via its sparse index
+const CRATES_IO_SPARSE_INDEX: &str = "https://index.crates.io/";
 
-impl Version {
-    fn is_available(&self, prerelease: bool) -> bool {
-        if !prerelease && !self.num.pre.is_empty() {
-            return false;
-        }
-        !self.yanked
-    }
-}
+/// Base URL of the crates.io web API
+const CRATES_IO_API: &str = "https://crates.io/api/v1";
 
-//--------------------------------------------------------------------------------------------------
+/// Prefixes of the crates.io directory names under `$CARGO_HOME/registry/index`
+const CRATES_IO_CACHE_PREFIXES: [&str; 2] = ["index.crates.io-", "github.com-1ecc6299db9ec823"];
 
-/**
-Get the latest available (not prerelease or yanked) version(s) for a crate, optionally matching a
-required version
+/// Alternative registry an external crate was installed from
+#[derive(Debug, Serialize, Eq, PartialEq, Clone)]
+pub struct Registry {
+    /// Name in the `[registries]` table of `$CARGO_HOME/config.toml`, if configured
+    pub name: Option<String>,
 
-# Errors
+    /// Index URL, with a `sparse+` prefix for sparse registries
+    pub index: String,
+}
+
+impl Registry {
+    /**
+    Create a registry from a `registry+URL` or `sparse+URL` source; return `None` for crates.io or
+    other sources
+    */
+    fn from(source: &str, registries: &BTreeMap<String, String>) -> Option<Registry> {
+        let index = if let Some(url) = source.strip_prefix("registry+") {
+            if url == CRATES_IO_INDEX {
+                return None;
+            }
+            url
+        } else if source.starts_with("sparse+") {
+            if source.strip_prefix("sparse+") == Some(CRATES_IO_SPARSE_INDEX) {
+                return None;
+            }
+            source
+        } else {
+            return None;
+        };
+        let name = registries
+            .iter()
+            .find(|(_name, url)| url.trim_end_matches('/') == index.trim_end_matches('/'))
+            .map(|(name, _url)| name.clone());
+        Some(Registry {
+            name,
+            index: index.to_string(),
+        })
+    }
+
+    /// Prefix of the registry's directory name under `$CARGO_HOME/registry/index`
+    fn cache_prefix(&self) -> Option<String> {
+        let url = reqwest::Url::parse(self.index.trim_start_matches("sparse+")).ok()?;
+        Some(format!("{}-", url.host_str()?))
+    }
+
+    /// Token from the `CARGO_REGISTRIES_<NAME>_TOKEN` environment variable, if set
+    fn token(&self) -> Option<String> {
+        let name = self.name.as_ref()?.to_uppercase().replace('-', "_");
+        std::env::var(format!("CARGO_REGISTRIES_{name}_TOKEN")).ok()
+    }
+}
+
+/// Subset of cargo's configuration file (`$CARGO_HOME/config.toml`)
+#[derive(Debug, Default, Deserialize)]
+struct CargoConfig {
+    #[serde(default)]
+    registries: BTreeMap<String, CargoConfigRegistry>,
+
+    #[serde(default)]
+    source: BTreeMap<String, CargoConfigSource>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CargoConfigRegistry {
+    index: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct CargoConfigSource {
+    replace_with: Option<String>,
+    registry: Option<String>,
+    local_registry: Option<PathBuf>,
+}
+
+impl CargoConfig {
+    /**
+    Load `$CARGO_HOME/config.toml` (or the legacy `$CARGO_HOME/config`); return the default if it
+    does not exist
+
+    # Errors
+
+    Returns an error if not able to read or parse the file
+    */
+    fn load() -> Result<CargoConfig> {
+        let home = cargo_home();
+        for path in [home.join("config.toml"), home.join("config")] {
+            if path.exists() {
+                return toml::from_str(&std::fs::read_to_string(&path)?)
+                    .with_context(|| format!("Failed to parse `{}`", path.display()));
+            }
+        }
+        Ok(CargoConfig::default())
+    }
+
+    /**
+    Follow the `[source.crates-io]` `replace-with` chain to the index URL of a sparse or local
+    registry mirror, if any
+    */
+    fn crates_io_mirror(&self) -> Option<String> {
+        let mut name = "crates-io";
+        for _ in 0..=self.source.len() {
+            if let Some(registry) = self.registries.get(name) {
+                return registry.index.clone().filter(|x| x.starts_with("sparse+"));
+            }
+            let source = self.source.get(name)?;
+            if let Some(replace_with) = &source.replace_with {
+                name = replace_with;
+            } else if let Some(path) = &source.local_registry {
+                return reqwest::Url::from_directory_path(path.join("index"))
+                    .ok()
+                    .map(String::from);
+            } else {
+                return source.registry.clone().filter(|x| x.starts_with("sparse+"));
+            }
+        }
+        None
+    }
+
+    /// Map registry names to index URLs
+    fn registries(&self) -> BTreeMap<String, String> {
+        self.registries
+            .iter()
+            .filter_map(|(name, registry)| Some((name.clone(), registry.index.clone()?)))
+            .collect()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+
+/// Backend used to get the available versions of a crate
+#[derive(Debug, Default, Deserialize, Eq, PartialEq, Hash, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Sparse registry index protocol (`https://index.crates.io`), like cargo itself
+    #[default]
+    Index,
+
+    /// crates.io web API (`https://crates.io/api/v1`)
+    Api,
+}
+
+/// Installer used to update a crate
+#[derive(Debug, Default, Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Installer {
+    /// Build from source via `cargo install`
+    #[default]
+    Cargo,
+
+    /// Install a prebuilt binary via [`cargo binstall`](https://github.com/cargo-bins/cargo-binstall)
+    Binstall,
+}
+
+/// Options for loading installed crates
+#[derive(Debug, Default, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Options {
+    /// Backend used to get the available versions of each crate
+    pub backend: Backend,
+
+    /**
+    Directory to cache registry responses and the git indexes of alternative registries in (see
+    [`cache_dir()`]); `None` disables the cache
+    */
+    pub cache_dir: Option<PathBuf>,
+
+    /**
+    Use cached registry responses younger than this without revalidating them; older responses
+    are revalidated via a conditional request (`If-None-Match` / `If-Modified-Since`)
+    */
+    pub cache_ttl: Duration,
+
+    /// Ignore cached registry responses and fetch them again (the cache is still updated)
+    pub refresh: bool,
+
+    /**
+    Base URL of the crates.io sparse index (default: `https://index.crates.io/`) or web API
+    (default: `https://crates.io/api/v1`) per the [`Backend`], for example a mirror or a local
+    stand-in; `file://` URLs read the same layout from a directory
+
+    If unset, the `CARGO_LIST_REGISTRY_URL` environment variable is used, or, for the sparse index,
+    a sparse or local registry configured to replace crates.io via `[source.crates-io]` in
+    `$CARGO_HOME/config.toml`.
+    */
+    pub registry_url: Option<String>,
+
+    /// Resolve available versions from the local registry index cache
+    /// (`$CARGO_HOME/registry/index/*/.cache`) instead of the network; results may be stale
+    pub offline: bool,
+
+    /**
+    Check crates installed via git for new commits on the tracked branch or tag via
+    `git ls-remote` (ignored if `offline`)
+    */
+    pub git: bool,
+
+    /// Check crates installed via `cargo install --path` for a changed version in their `Cargo.toml`
+    pub local: bool,
+
+    /**
+    Also consider a local crate outdated if any of its source files were modified after its
+    binaries were installed (requires `local`)
+    */
+    pub local_mtime: bool,
+
+    /// Maximum number of crates resolved (registry requests in flight) at once; `0` uses the number
+    /// of CPUs
+    pub max_requests: usize,
+
+    /**
+    Retry a registry request this many times after a connection error, timeout, `429 Too Many
+    Requests` or server error, with exponential backoff or per the `Retry-After` header
+    */
+    pub retries: u32,
+
+    /// Timeout for each registry request
+    pub timeout: Option<Duration>,
+
+    /// Timeout for all registry requests, including retries
+    pub total_timeout: Option<Duration>,
+
+    /**
+    Fail if any crate fails to process instead of recording the error in [`Crate::error`] and
+    returning the other crates
+    */
+    pub strict: bool,
+
+    /**
+    Cap the available version of each external crate at the newest version that the active
+    toolchain can build per its `rust-version`
+    */
+    pub msrv: bool,
+
+    /**
+    Local clone of the [RustSec advisory database](https://github.com/rustsec/advisory-db) to check
+    the installed versions of crates.io crates against (see [`advisory_db()`])
+    */
+    pub advisory_db: Option<PathBuf>,
+
+    /// Per-crate policies by crate name
+    pub policies: BTreeMap<String, Policy>,
+
+    /// Installer used to update crates unless overridden by their [`Policy`] (see
+    /// [`Crate::installer`])
+    pub installer: Installer,
+
+    /// Update crates with `--locked` unless overridden by their [`Policy`]
+    pub locked: bool,
+
+    /**
+    Update crates with all their binaries instead of only the installed ones (`--bin`) unless
+    overridden by their [`Policy`]
+    */
+    pub all_bins: bool,
+
+    /// Exclude crates with names matching any of these patterns
+    pub exclude: Vec<String>,
+}
+
+//--------------------------------------------------------------------------------------------------
+
+/**
+User configuration file with defaults and per-crate policies
+
+```toml
+kinds = ["local", "git", "external"]
+format = "md"
+outdated = true
+ignore-req = false
+outdated-rust = false
+installer = "binstall"
+locked = true
+all-bins = false
+
+[registry]
+backend = "index"
+url = "sparse+https://mirror.example.com/index/"
+offline = false
+ttl = 300
+max-requests = 8
+retries = 3
+timeout = 30
+total-timeout = 120
+
+[crates.ripgrep]
+hold = "~14.1"
+
+[crates.some-crate]
+ignore = true
+
+[crates.other-crate]
+prerelease = true
+installer = "cargo"
+locked = false
+all-bins = true
+```
+*/
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// Kinds of crates to list
+    pub kinds: Option<Vec<Kind>>,
+
+    /// Output format (`md`, `json`, `json-pretty`, `rust`, `rust-pretty`)
+    pub format: Option<String>,
+
+    /// Hide up-to-date crates
+    pub outdated: Option<bool>,
+
+    /// Ignore version requirements
+    pub ignore_req: Option<bool>,
+
+    /// Consider a crate outdated if compiled with a Rust version different than the active
+    /// toolchain
+    pub outdated_rust: Option<bool>,
+
+    /// Installer used to update crates (`cargo` or `binstall`)
+    pub installer: Option<Installer>,
+
+    /// Update crates with `--locked`
+    pub locked: Option<bool>,
+
+    /// Update crates with all their binaries instead of only the installed ones
+    pub all_bins: Option<bool>,
+
+    /// Registry settings
+    pub registry: RegistryConfig,
+
+    /// Per-crate policies by crate name
+    pub crates: BTreeMap<String, Policy>,
+}
+
+/// Registry settings in the [`Config`] file (see the corresponding [`Options`] fields)
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RegistryConfig {
+    pub backend: Option<Backend>,
+    pub url: Option<String>,
+    pub offline: Option<bool>,
+
+    /// Cache TTL in seconds
+    pub ttl: Option<u64>,
+
+    pub max_requests: Option<usize>,
+    pub retries: Option<u32>,
+
+    /// Timeout for each request in seconds
+    pub timeout: Option<u64>,
+
+    /// Timeout for all requests in seconds
+    pub total_timeout: Option<u64>,
+}
+
+/// Per-crate policy in the [`Config`] file
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Policy {
+    /// Skip the crate entirely
+    pub ignore: bool,
+
+    /// Hold the crate at versions matching this requirement (for example, `=1.2.3` or `~1.2`)
+    pub hold: Option<String>,
+
+    /// Consider prerelease versions even if a stable version is installed
+    pub prerelease: bool,
+
+    /// Installer used to update the crate
+    pub installer: Option<Installer>,
+
+    /// Update the crate with `--locked`
+    pub locked: Option<bool>,
+
+    /// Update the crate with all its binaries instead of only the installed ones
+    pub all_bins: Option<bool>,
+}
+
+impl Config {
+    /**
+    Load the configuration file at [`config_path()`], if it exists
+
+    # Errors
+
+    Returns an error if not able to read or parse the file, or if `$CARGO_LIST_CONFIG` is set and
+    the file does not exist
+    */
+    pub fn load() -> Result<Config, Error> {
+        match config_path() {
+            Some(path) if path.exists() || std::env::var_os("CARGO_LIST_CONFIG").is_some() => {
+                Config::from_path(&path)
+            }
+            _ => Ok(Config::default()),
+        }
+    }
+
+    /**
+    Load a configuration file
+
+    # Errors
+
+    Returns an error if not able to read or parse the file
+    */
+    pub fn from_path(path: &Path) -> Result<Config, Error> {
+        let text = std::fs::read_to_string(path).map_err(|source| {
+            if source.kind() == std::io::ErrorKind::NotFound {
+                Error::NotFound {
+                    path: path.to_path_buf(),
+                }
+            } else {
+                Error::Read {
+                    path: path.to_path_buf(),
+                    source,
+                }
+            }
+        })?;
+        toml::from_str(&text).map_err(|source| Error::InvalidConfig {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /**
+    Get the default [`Options`] with the update settings, registry settings, and per-crate policies
+    applied
+
+    The caller overrides the configuration file: set fields via struct update syntax
+    (`Options { offline: false, ..config.options() }`) and add or replace policies by extending
+    [`Options::policies`].
+    */
+    #[must_use]
+    pub fn options(&self) -> Options {
+        let registry = &self.registry;
+        let options = Options::default();
+        Options {
+            backend: registry.backend.unwrap_or(options.backend),
+            registry_url: self.registry_url(),
+            offline: registry.offline.unwrap_or(options.offline),
+            cache_ttl: registry.ttl.map_or(options.cache_ttl, Duration::from_secs),
+            max_requests: registry.max_requests.unwrap_or(options.max_requests),
+            retries: registry.retries.unwrap_or(options.retries),
+            timeout: registry
+                .timeout
+                .map(Duration::from_secs)
+                .or(options.timeout),
+            total_timeout: registry
+                .total_timeout
+                .map(Duration::from_secs)
+                .or(options.total_timeout),
+            policies: self.crates.clone(),
+            installer: self.installer.unwrap_or(options.installer),
+            locked: self.locked.unwrap_or(options.locked),
+            all_bins: self.all_bins.unwrap_or(options.all_bins),
+            ..options
+        }
+    }
+
+    /// Get the registry URL unless overridden by the `CARGO_LIST_REGISTRY_URL` environment variable
+    #[must_use]
+    pub fn registry_url(&self) -> Option<String> {
+        std::env::var("CARGO_LIST_REGISTRY_URL")
+            .ok()
+            .or_else(|| self.registry.url.clone())
+    }
+}
+
+/// Options and state shared while resolving the available versions of all crates
+struct Resolver {
+    /// Options with the effective registry URL
+    options: Options,
+
+    /// Registry names and index URLs from `$CARGO_HOME/config.toml`
+    registries: BTreeMap<String, String>,
+
+    /// Deadline for all registry requests per [`Options::total_timeout`]
+    deadline: Option<Instant>,
+
+    /// Directory containing the installed binaries
+    bin_dir: Option<PathBuf>,
+
+    /// Repositories of the git indexes fetched in this run by index URL (see
+    /// [`Resolver::git_index`])
+    git_indexes: Mutex<BTreeMap<String, PathBuf>>,
+}
+
+impl Resolver {
+    /**
+    Create a resolver from the given options and `$CARGO_HOME/config.toml`
+
+    # Errors
+
+    Returns an error if not able to read or parse `$CARGO_HOME/config.toml`
+    */
+    fn new(options: &Options) -> Result<Resolver> {
+        if let Some(db) = &options.advisory_db
+            && !db.join("crates").is_dir()
+        {
+            return Err(anyhow!(
+                "Failed to find the advisory database at `{}`",
+                db.display()
+            ));
+        }
+        let config = CargoConfig::load()?;
+        Ok(Resolver {
+            options: Options {
+                registry_url: options
+                    .registry_url
+                    .clone()
+                    .or_else(|| std::env::var("CARGO_LIST_REGISTRY_URL").ok())
+                    .or_else(|| {
+                        if options.backend == Backend::Index {
+                            config.crates_io_mirror()
+                        } else {
+                            None
+                        }
+                    }),
+                ..options.clone()
+            },
+            registries: config.registries(),
+            deadline: options.total_timeout.map(|x| Instant::now() + x),
+            bin_dir: None,
+            git_indexes: Mutex::default(),
+        })
+    }
+
+    /**
+    Get a bare repository with the tip of a registry's git index, fetched via a shallow `git fetch`
+    at most once per run
+
+    The repository is kept under [`Options::cache_dir`] (or the temporary directory) and not
+    fetched again within [`Options::cache_ttl`] unless [`Options::refresh`] is set.
+
+    # Errors
+
+    Returns an error if `git init` or `git fetch` fails
+    */
+    fn git_index(&self, registry: &Registry) -> Result<PathBuf> {
+        let mut fetched = self
+            .git_indexes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(dir) = fetched.get(&registry.index) {
+            return Ok(dir.clone());
+        }
+        let options = &self.options;
+        let slug = registry
+            .index
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect::<String>();
+        let dir = options
+            .cache_dir
+            .as_ref()
+            .map_or_else(|| std::env::temp_dir().join("cargo-list"), Clone::clone)
+            .join("git-index")
+            .join(slug);
+        let fresh = options.cache_dir.is_some()
+            && !options.refresh
+            && dir
+                .join("FETCH_HEAD")
+                .metadata()
+                .and_then(|x| x.modified())
+                .ok()
+                .and_then(|x| x.elapsed().ok())
+                .is_some_and(|x| x < options.cache_ttl);
+        if !fresh {
+            if !dir.join("HEAD").exists() {
+                std::fs::create_dir_all(&dir)
+                    .with_context(|| format!("Failed to create `{}`", dir.display()))?;
+                git(&dir, &["init", "-q", "--bare"])?;
+            }
+            git(
+                &dir,
+                &["fetch", "-q", "--depth", "1", &registry.index, "HEAD"],
+            )?;
+        }
+        fetched.insert(registry.index.clone(), dir.clone());
+        Ok(dir)
+    }
+
+    /**
+    Send a request, retrying per [`Options::retries`] within the per-request and total timeouts
+
+    # Errors
+
+    Returns an error if the request fails (after retries) or the total timeout expired
+    */
+    fn send(&self, req: &RequestBuilder) -> Result<Response> {
+        let mut attempt = 0;
+        loop {
+            let remaining = self
+                .deadline
+                .map(|x| x.saturating_duration_since(Instant::now()));
+            if remaining.is_some_and(|x| x.is_zero()) {
+                return Err(anyhow!("Registry requests timed out"));
+            }
+            let mut this = req
+                .try_clone()
+                .ok_or_else(|| anyhow!("Failed to clone the request"))?;
+            if let Some(timeout) = [self.options.timeout, remaining]
+                .into_iter()
+                .flatten()
+                .min()
+            {
+                this = this.timeout(timeout);
+            }
+            let res = this.send();
+            let retry = match &res {
+                Ok(res) => {
+                    res.status() == StatusCode::TOO_MANY_REQUESTS || res.status().is_server_error()
+                }
+                Err(e) => e.is_connect() || e.is_timeout(),
+            };
+            let delay = res
+                .as_ref()
+                .ok()
+                .and_then(retry_after)
+                .unwrap_or_else(|| backoff(attempt));
+            if !retry || attempt >= self.options.retries || remaining.is_some_and(|x| delay >= x) {
+                return Ok(res?);
+            }
+            std::thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+/// Exponential backoff delay before retrying: 0.5s, 1s, 2s, ... up to 30s
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(500)
+        .saturating_mul(2_u32.saturating_pow(attempt))
+        .min(Duration::from_secs(30))
+}
+
+/// Delay requested via the `Retry-After` header (in seconds) of a response, if any, up to 5 minutes
+fn retry_after(res: &Response) -> Option<Duration> {
+    let seconds = res
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(Duration::from_secs(seconds).min(Duration::from_mins(5)))
+}
+
+//--------------------------------------------------------------------------------------------------
+
+/// All installed crates
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Crates {
+    installs: BTreeMap<String, Crate>,
+
+    /**
+    Installation root of the metadata file if it is not in the cargo home directory (see
+    [`Crate::root`])
+    */
+    #[serde(skip)]
+    pub root: Option<PathBuf>,
+
+    #[serde(skip)]
+    pub active_toolchain: String,
+
+    #[serde(skip)]
+    pub active_version: String,
+}
+
+impl Crates {
+    /**
+    Deserialize from a `~/.cargo/.crates2.json` file and process each crate in
+    parallel to:
+
+    * Parse the name, version, source, rust version
+    * Get the latest avaiable version
+    * Determine the crate type
+
+    # Errors
+
+    Returns an error if not able to read the file at the given path
+    */
+    pub fn from(path: &Path) -> Result<Crates, Error> {
+        Crates::from_include(path, &[])
+    }
+
+    /**
+    Return true if no crates are installed
+    */
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.installs.is_empty()
+    }
+
+    /// Return a view of all crates
+    #[must_use]
+    pub fn crates(&self) -> BTreeMap<&str, &Crate> {
+        self.installs
+            .values()
+            .map(|x| (x.name.as_str(), x))
+            .collect()
+    }
+
+    /**
+    Like the [`Crates::from`] method, but accepts zero or more include patterns to match against
+    crate names
+
+    # Errors
+
+    Returns an error if not able to read the file at the given path or a pattern is not a valid
+    regular expression
+    */
+    pub fn from_include(path: &Path, patterns: &[&str]) -> Result<Crates, Error> {
+        Crates::from_options(path, patterns, &Options::default())
+    }
+
+    /**
+    Like the [`Crates::from_include`] method, but also accepts zero or more exclude patterns; crates
+    with names matching any exclude pattern are skipped even if they match an include pattern
+
+    # Errors
+
+    Returns an error if not able to read the file at the given path or a pattern is not a valid
+    regular expression
+    */
+    pub fn from_filters(path: &Path, include: &[&str], exclude: &[&str]) -> Result<Crates, Error> {
+        Crates::from_options(
+            path,
+            include,
+            &Options {
+                exclude: exclude.iter().map(ToString::to_string).collect(),
+                ..Default::default()
+            },
+        )
+    }
+
+    /**
+    Like the [`Crates::from_include`] method, but accepts [`Options`]
+
+    A crate that fails to process (for example, it was removed from its registry or the registry is
+    unreachable) has its [`Crate::error`] set unless [`Options::strict`] is set.
+
+    # Errors
+
+    Returns an [`Error`] if not able to read or parse the file at the given path, a pattern is not a
+    valid regular expression, or any crate fails to process in strict mode ([`Error::Crates`])
+    */
+    pub fn from_options(
+        path: &Path,
+        patterns: &[&str],
+        options: &Options,
+    ) -> Result<Crates, Error> {
+        let mut crates = Crates::read(path, patterns, options)?;
+        crates.active_toolchain = active_toolchain();
+        crates.active_version = crates
+            .active_toolchain
+            .lines()
+            .find_map(|line| {
+                line.split(' ')
+                    .skip_while(|&word| word != "rustc")
+                    .nth(1)
+                    .map(ToString::to_string)
+            })
+            .unwrap_or_default();
+        let mut resolver = Resolver::new(options)?;
+        resolver.bin_dir = path.parent().map(|x| x.join("bin"));
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(options.max_requests)
+            .build()
+            .map_err(anyhow::Error::from)?;
+        let errors = pool.install(|| {
+            crates
+                .installs
+                .par_iter_mut()
+                .filter_map(|(k, v)| {
+                    let e = v.init(k, &crates.active_version, &resolver).err()?;
+                    Some(v.failed(k, e))
+                })
+                .collect::<Vec<_>>()
+        });
+        crates.check(errors, options.strict)
+    }
+
+    /**
+    Like the [`Crates::from_options`] method, but only parses the installed crates without checking
+    for available versions (no network access)
+
+    # Errors
+
+    Returns an [`Error`] if not able to read or parse the file at the given path, a pattern is not a
+    valid regular expression, or any crate fails to parse in strict mode ([`Error::Crates`])
+    */
+    pub fn parse(path: &Path, patterns: &[&str], options: &Options) -> Result<Crates, Error> {
+        let mut crates = Crates::read(path, patterns, options)?;
+        let registries = CargoConfig::load()?.registries();
+        let errors = crates
+            .installs
+            .par_iter_mut()
+            .filter_map(|(k, v)| {
+                let e = v.parse(k, options, &registries).err()?;
+                Some(v.failed(k, e))
+            })
+            .collect::<Vec<_>>();
+        crates.check(errors, options.strict)
+    }
+
+    /// Read the file at the given path and filter the crates per the patterns and [`Options`]
+    fn read(path: &Path, patterns: &[&str], options: &Options) -> Result<Crates, Error> {
+        let file = File::open(path).map_err(|source| {
+            if source.kind() == std::io::ErrorKind::NotFound {
+                Error::NotFound {
+                    path: path.to_path_buf(),
+                }
+            } else {
+                Error::Read {
+                    path: path.to_path_buf(),
+                    source,
+                }
+            }
+        })?;
+        let mut crates: Crates =
+            serde_json::from_reader(std::io::BufReader::new(file)).map_err(|source| {
+                Error::Parse {
+                    path: path.to_path_buf(),
+                    source,
+                }
+            })?;
+        if !patterns.is_empty() {
+            let set = RegexSet::new(patterns)?;
+            crates.installs = crates
+                .installs
+                .into_par_iter()
+                .filter_map(|(k, v)| {
+                    if set.is_match(k.split_once(' ').map_or(k.as_str(), |x| x.0)) {
+                        Some((k, v))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+        }
+        let exclude = RegexSet::new(&options.exclude)?;
+        crates.installs.retain(|k, _| {
+            let name = k.split_once(' ').map_or(k.as_str(), |x| x.0);
+            !exclude.is_match(name) && !options.policies.get(name).is_some_and(|x| x.ignore)
+        });
+
+        // Update crates in the installation root of a metadata file outside the cargo home
+        let canonical = |x: &Path| x.canonicalize().unwrap_or_else(|_| x.to_path_buf());
+        let root = path
+            .parent()
+            .map(|x| {
+                if x.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    x
+                }
+            })
+            .map(canonical)
+            .filter(|x| *x != canonical(&cargo_home()));
+        for c in crates.installs.values_mut() {
+            c.root.clone_from(&root);
+        }
+        crates.root = root;
+
+        Ok(crates)
+    }
+
+    /// Fail with the errors of the crates that failed to process in strict mode
+    fn check(self, errors: Vec<Error>, strict: bool) -> Result<Crates, Error> {
+        if errors.is_empty() || !strict {
+            Ok(self)
+        } else {
+            Err(Error::Crates(errors))
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+
+/// Individual installed crate
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Crate {
+    #[serde(skip_deserializing)]
+    pub name: String,
+
+    #[serde(skip_deserializing)]
+    pub kind: Kind,
+
+    #[serde(skip_deserializing)]
+    pub installed: String,
+
+    #[serde(skip_deserializing)]
+    installed_: Option<semver::Version>,
+
+    #[serde(skip_deserializing)]
+    prerelease: bool,
+
+    #[serde(skip_deserializing)]
+    pub available: String,
+
+    #[serde(skip_deserializing)]
+    pub newer: Vec<String>,
+
+    /// Newest available version that the active toolchain can build (`None` if none)
+    #[serde(skip_deserializing)]
+    pub compatible: Option<String>,
+
+    /// Available version was capped at the compatible version per [`Options::msrv`]
+    #[serde(skip_deserializing)]
+    pub capped: bool,
+
+    /// Installed version has since been yanked from the registry
+    #[serde(skip_deserializing)]
+    pub installed_yanked: bool,
+
+    /// Nearest available version to move a yanked install to (`None` unless yanked)
+    #[serde(skip_deserializing)]
+    pub replacement: Option<String>,
+
+    /// Version requirement the crate is held at per its [`Policy`]
+    #[serde(skip_deserializing)]
+    pub hold: Option<String>,
+
+    /**
+    Installer used to update the crate per its [`Policy`] or [`Options::installer`]
+
+    Falls back to [`Installer::Cargo`] for git and local crates and crates installed with features
+    or a profile other than `release`, which `cargo binstall` cannot honor.
+    */
+    #[serde(skip_deserializing)]
+    pub installer: Installer,
+
+    /// Update with `--locked` per its [`Policy`] or [`Options::locked`]
+    #[serde(skip_deserializing)]
+    pub locked: bool,
+
+    /**
+    Update with all binaries instead of only the installed ones per its [`Policy`] or
+    [`Options::all_bins`]
+    */
+    #[serde(skip_deserializing)]
+    pub all_bins: bool,
+
+    /**
+    Installation root to update the crate in if the metadata file is not in the cargo home
+    directory (see [`cargo_home()`])
+    */
+    #[serde(skip_deserializing)]
+    pub root: Option<PathBuf>,
+
+    /// Class of the update to the available version (`None` if up-to-date or not checked)
+    #[serde(skip_deserializing)]
+    pub update: Option<Update>,
+
+    /// Security advisories affecting the installed version (if checked)
+    #[serde(skip_deserializing)]
+    pub advisories: Vec<Advisory>,
+
+    #[serde(skip_deserializing)]
+    pub rust_version: String,
+
+    #[serde(skip_deserializing)]
+    pub outdated: bool,
+
+    #[serde(skip_deserializing)]
+    pub outdated_rust: bool,
+
+    /// Available versions were resolved offline and may be stale
+    #[serde(skip_deserializing)]
+    pub stale: bool,
+
+    /// Alternative registry the crate was installed from (`None` for crates.io)
+    #[serde(skip_deserializing)]
+    pub registry: Option<Registry>,
+
+    /// Git repository and commits (`None` unless installed via git)
+    #[serde(skip_deserializing)]
+    pub git: Option<GitSource>,
+
+    /// Source directory and version (`None` unless a local crate that was checked)
+    #[serde(skip_deserializing)]
+    pub local: Option<LocalSource>,
+
+    /// Reason the crate failed to process (`None` if processed successfully)
+    #[serde(skip_deserializing)]
+    pub error: Option<String>,
+
+    #[serde(skip_deserializing)]
+    source: String,
+
+    pub version_req: Option<String>,
+    bins: Vec<String>,
+    features: Vec<String>,
+    all_features: bool,
+    no_default_features: bool,
+    profile: String,
+    target: String,
+    rustc: String,
+}
+
+impl Crate {
+    /// Initialize additional fields after deserialization
+    fn init(&mut self, k: &str, active_version: &str, resolver: &Resolver) -> Result<(), Error> {
+        self.parse(k, &resolver.options, &resolver.registries)?;
+
+        // Unknown if not able to get the active toolchain
+        self.outdated_rust = !active_version.is_empty() && self.rust_version != active_version;
+
+        if let Some(git) = &mut self.git
+            && resolver.options.git
+            && !resolver.options.offline
+        {
+            git.remote_commit = Some(git.fetch_remote_commit()?);
+            self.outdated = git.is_outdated();
+        }
+
+        if self.kind == Local && resolver.options.local {
+            let mut local = LocalSource::from(&self.source)?;
+            if resolver.options.local_mtime
+                && let Some(bin_dir) = &resolver.bin_dir
+            {
+                let installed = self
+                    .bins
+                    .iter()
+                    .filter_map(|bin| {
+                        let path = bin_dir.join(bin);
+                        path.metadata()
+                            .or_else(|_| path.with_extension("exe").metadata())
+                            .and_then(|x| x.modified())
+                            .ok()
+                    })
+                    .min();
+                local.modified = installed
+                    .zip(newest_mtime(&local.path))
+                    .is_some_and(|(installed, modified)| modified > installed);
+            }
+            self.available.clone_from(&local.version);
+            self.outdated = self.installed != local.version || local.modified;
+            self.local = Some(local);
+        }
+
+        if self.kind == External
+            && self.registry.is_none()
+            && let Some(db) = &resolver.options.advisory_db
+            && let Some(installed) = &self.installed_
+        {
+            self.advisories = Advisory::load(db, &self.name, installed)?;
+        }
+
+        if self.kind == External {
+            self.resolve(active_version, resolver)?;
+        }
+
+        Ok(())
+    }
+
+    /// Parse the key and the fields that do not require checking for available versions
+    fn parse(
+        &mut self,
+        k: &str,
+        options: &Options,
+        registries: &BTreeMap<String, String>,
+    ) -> Result<(), Error> {
+        let malformed = |reason: String| Error::MalformedEntry {
+            key: k.to_string(),
+            reason,
+        };
+
+        // Key: `name version (source)`
+        let (name, rest) = k.split_once(' ').unwrap_or((k, ""));
+        self.name = name.to_string();
+        if !is_valid_name(name) {
+            return Err(malformed(format!("invalid crate name `{name}`")));
+        }
+        let (installed, source) = rest
+            .split_once(' ')
+            .ok_or_else(|| malformed(String::from("expected `name version (source)`")))?;
+        self.installed = installed.to_string();
+        self.source = source
+            .strip_prefix('(')
+            .and_then(|x| x.strip_suffix(')'))
+            .ok_or_else(|| malformed(format!("source `{source}` is not in parentheses")))?
+            .to_string();
+        self.installed_ = semver::Version::parse(&self.installed).ok();
+        self.prerelease = self.installed_.as_ref().is_some_and(|x| !x.pre.is_empty());
+        let policy = options.policies.get(&self.name);
+        if let Some(policy) = policy {
+            self.hold.clone_from(&policy.hold);
+            self.prerelease |= policy.prerelease;
+        }
+
+        self.kind = Kind::from(&self.source);
+        self.installer = policy
+            .and_then(|x| x.installer)
+            .unwrap_or(options.installer);
+        self.locked = policy.and_then(|x| x.locked).unwrap_or(options.locked);
+        self.all_bins = policy.and_then(|x| x.all_bins).unwrap_or(options.all_bins);
+        if self.kind != External
+            || !self.features.is_empty()
+            || self.all_features
+            || self.no_default_features
+            || self.profile != "release"
+        {
+            self.installer = Installer::Cargo;
+        }
+        self.registry = Registry::from(&self.source, registries);
+
+        self.rust_version = self
+            .rustc
+            .strip_prefix("rustc ")
+            .and_then(|x| x.split(' ').next())
+            .filter(|x| !x.is_empty())
+            .ok_or_else(|| malformed(format!("unexpected rustc version `{}`", self.rustc)))?
+            .to_string();
+
+        if self.kind == Git {
+            self.git = Some(
+                GitSource::from(&self.source)
+                    .ok_or_else(|| anyhow!("Failed to parse the git source `{}`", self.source))?,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Record the error of a crate that failed to process and wrap it with the crate's key
+    fn failed(&mut self, k: &str, e: Error) -> Error {
+        self.error = Some(error_chain(&e));
+        Error::Crate {
+            key: k.to_string(),
+            source: Box::new(e),
+        }
+    }
+
+    /// Get the available versions of an external crate and determine whether it is outdated
+    fn resolve(&mut self, active_version: &str, resolver: &Resolver) -> Result<(), Error> {
+        let versions =
+            Versions::fetch(&self.name, self.registry.as_ref(), resolver).map_err(|source| {
+                Error::Registry {
+                    name: self.name.clone(),
+                    source,
+                }
+            })?;
+        self.stale = versions.stale;
+        if let Some(installed) = &self.installed_ {
+            self.installed_yanked = versions.is_yanked(installed);
+            if self.installed_yanked {
+                self.replacement = versions.nearest(installed, self.prerelease);
+            }
+        }
+        let latest = match versions.latest(
+            &self.name,
+            self.hold.as_deref().or(self.version_req.as_deref()),
+            self.prerelease,
+            Some(active_version).filter(|x| !x.is_empty()),
+        ) {
+            // A yanked install pinned to its exact version has no other matching version
+            Err(Error::NoMatchingVersion { .. }) if self.installed_yanked => return Ok(()),
+            latest => latest?,
+        };
+        self.available = latest.version;
+        self.newer = latest.newer;
+        self.compatible = latest.compatible;
+        self.classify();
+        if resolver.options.msrv && self.compatible.as_ref() != Some(&self.available) {
+            // Never downgrade to a compatible version older than the installed one
+            let newer = |x: &String| {
+                semver::Version::parse(x)
+                    .ok()
+                    .zip(self.installed_.as_ref())
+                    .is_none_or(|(x, installed)| x > *installed)
+            };
+            if let Some(compatible) = self.compatible.clone().filter(newer) {
+                self.available = compatible;
+                self.capped = true;
+                self.classify();
+            } else {
+                self.outdated = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+    Classify the update to the available version and determine whether the crate is outdated (not
+    if the installed version is ahead)
+    */
+    fn classify(&mut self) {
+        if let Some((available, installed)) = semver::Version::parse(&self.available)
+            .ok()
+            .zip(self.installed_.as_ref())
+        {
+            self.update = Update::classify(installed, &available);
+            self.outdated = self.update.is_some_and(|x| x != Update::Ahead);
+        } else {
+            self.update = None;
+            self.outdated = self.installed != self.available;
+        }
+    }
+
+    /**
+    Generate the cargo install command to update the crate
+
+    A yanked install that is not otherwise outdated is moved to its [`Crate::replacement`].
+    */
+    #[must_use]
+    pub fn update_command(&self, pinned: bool) -> Vec<String> {
+        let exact = if self.capped {
+            Some(format!("={}", self.available))
+        } else if self.installed_yanked && !self.outdated {
+            self.replacement.as_ref().map(|x| format!("={x}"))
+        } else {
+            None
+        };
+        let version = exact.as_ref().or(self.hold.as_ref()).or(if pinned {
+            None
+        } else {
+            self.version_req.as_ref()
+        });
+        self.install_command(version.map(String::as_str))
+    }
+
+    /// Generate the cargo install command to install the crate at the given version requirement
+    fn install_command(&self, version: Option<&str>) -> Vec<String> {
+        if self.installer == Installer::Binstall {
+            return self.binstall_command(version);
+        }
+
+        let mut r = vec!["cargo", "install"];
+        let root;
+        let local_path;
+
+        if self.no_default_features {
+            r.push("--no-default-features");
+        }
+
+        let features = if self.features.is_empty() {
+            None
+        } else {
+            Some(self.features.join(","))
+        };
+        if let Some(features) = &features {
+            r.push("-F");
+            r.push(features);
+        }
+
+        if self.all_features {
+            r.push("--all-features");
+        }
+
+        if let Some(version) = version {
+            r.push("--version");
+            r.push(version);
+        }
+
+        if let Some(registry) = &self.registry {
+            if let Some(name) = &registry.name {
+                r.push("--registry");
+                r.push(name);
+            } else {
+                r.push("--index");
+                r.push(&registry.index);
+            }
+        }
+
+        r.push("--profile");
+        r.push(&self.profile);
+
+        r.push("--target");
+        r.push(&self.target);
+
+        if self.outdated_rust {
+            r.push("--force");
+        }
+
+        if self.locked {
+            r.push("--locked");
+        }
+
+        if let Some(path) = &self.root {
+            r.push("--root");
+            root = path.display().to_string();
+            r.push(&root);
+        }
+
+        if !self.all_bins {
+            for bin in &self.bins {
+                r.push("--bin");
+                r.push(bin);
+            }
+        }
+
+        if let Some(git) = &self.git {
+            r.push("--git");
+            r.push(&git.url);
+            match &git.reference {
+                GitReference::DefaultBranch => {}
+                GitReference::Branch(branch) => {
+                    r.push("--branch");
+                    r.push(branch);
+                }
+                GitReference::Tag(tag) => {
+                    r.push("--tag");
+                    r.push(tag);
+                }
+                GitReference::Rev(rev) => {
+                    r.push("--rev");
+                    r.push(rev);
+                }
+            }
+            r.push(&self.name);
+        } else if self.kind == Local
+            && let Some(path) = self
+                .source
+                .strip_prefix("path+")
+                .and_then(|x| reqwest::Url::parse(x).ok())
+                .and_then(|x| x.to_file_path().ok())
+        {
+            r.push("--path");
+            local_path = path.display().to_string();
+            r.push(&local_path);
+        } else {
+            r.push(&self.name);
+        }
+
+        r.into_iter().map(String::from).collect()
+    }
+
+    /// Generate the cargo binstall command to install the crate at the given version requirement
+    fn binstall_command(&self, version: Option<&str>) -> Vec<String> {
+        let mut r = vec!["cargo", "binstall", "--no-confirm"];
+
+        if let Some(version) = version {
+            r.push("--version");
+            r.push(version);
+        }
+
+        if let Some(registry) = &self.registry {
+            if let Some(name) = &registry.name {
+                r.push("--registry");
+                r.push(name);
+            } else {
+                r.push("--index");
+                r.push(&registry.index);
+            }
+        }
+
+        r.push("--targets");
+        r.push(&self.target);
+
+        if self.outdated_rust {
+            r.push("--force");
+        }
+
+        if self.locked {
+            r.push("--locked");
+        }
+
+        let root;
+        if let Some(path) = &self.root {
+            r.push("--root");
+            root = path.display().to_string();
+            r.push(&root);
+        }
+
+        r.push(&self.name);
+
+        r.into_iter().map(String::from).collect()
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+
+/// Version of the [`Manifest`] format
+pub const MANIFEST_VERSION: u32 = 1;
+
+/**
+Reproducible manifest of installed crates
+
+Create one from [`Crates`] (for example, via [`Crates::parse`]) and write it as TOML or JSON:
+
+```toml
+version = 1
+
+[crates.ripgrep]
+version = "14.1.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+bins = ["rg"]
+profile = "release"
+target = "x86_64-unknown-linux-gnu"
+```
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Format version ([`MANIFEST_VERSION`])
+    pub version: u32,
+
+    /// Installed crates by name
+    #[serde(default)]
+    pub crates: BTreeMap<String, ManifestCrate>,
+}
+
+/// Installed crate in a [`Manifest`]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ManifestCrate {
+    /// Installed version
+    pub version: String,
+
+    /// Version requirement given to `cargo install --version`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_req: Option<String>,
+
+    /// Cargo source (`registry+URL`, `sparse+URL`, `git+URL[?REF]#COMMIT`, or `path+file://PATH`)
+    pub source: String,
+
+    /// Installed commit (`None` unless installed via git)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub all_features: bool,
+
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub no_default_features: bool,
+
+    pub bins: Vec<String>,
+    pub profile: String,
+    pub target: String,
+
+    /// Rust compiler version the crate was built with (not used to sync)
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub rustc: String,
+}
+
+impl ManifestCrate {
+    /// Get the crate kind per its source
+    #[must_use]
+    pub fn kind(&self) -> Kind {
+        Kind::from(&self.source)
+    }
+
+    /**
+    Render the value of a field named as in [`ManifestCrate::differences`] or `rustc` (empty if
+    unset or unknown)
+    */
+    #[must_use]
+    pub fn value(&self, field: &str) -> String {
+        let sorted = |x: &[String]| {
+            let mut x = x.to_vec();
+            x.sort();
+            x.join(",")
+        };
+        match field {
+            "version" => self.version.clone(),
+            "source" => self
+                .source
+                .split_once('#')
+                .map_or(self.source.clone(), |x| x.0.to_string()),
+            "rev" => self.rev.clone().unwrap_or_default(),
+            "features" => sorted(&self.features),
+            "all-features" => self.all_features.to_string(),
+            "no-default-features" => self.no_default_features.to_string(),
+            "bins" => sorted(&self.bins),
+            "profile" => self.profile.clone(),
+            "target" => self.target.clone(),
+            "rustc" => self.rustc.clone(),
+            _ => String::new(),
+        }
+    }
+
+    /// Get the names of the fields that differ from the given installed crate
+    #[must_use]
+    pub fn differences(&self, installed: &ManifestCrate) -> Vec<&'static str> {
+        let sorted = |x: &[String]| {
+            let mut x = x.to_vec();
+            x.sort();
+            x
+        };
+        // The installed commit of a git source is compared as the `rev`
+        let source = |x: &ManifestCrate| {
+            x.source
+                .split_once('#')
+                .map_or(x.source.clone(), |x| x.0.to_string())
+        };
+        [
+            ("version", self.version != installed.version),
+            ("source", source(self) != source(installed)),
+            ("rev", self.rev.is_some() && self.rev != installed.rev),
+            (
+                "features",
+                sorted(&self.features) != sorted(&installed.features),
+            ),
+            ("all-features", self.all_features != installed.all_features),
+            (
+                "no-default-features",
+                self.no_default_features != installed.no_default_features,
+            ),
+            ("bins", sorted(&self.bins) != sorted(&installed.bins)),
+            ("profile", self.profile != installed.profile),
+            ("target", self.target != installed.target),
+        ]
+        .into_iter()
+        .filter_map(|(name, differs)| differs.then_some(name))
+        .collect()
+    }
+
+    /**
+    Create a [`Crate`] to generate its install command in the given installation root; crates
+    installed via git are pinned to the installed commit, and only the installed binaries are kept
+    */
+    fn to_crate(
+        &self,
+        name: &str,
+        registries: &BTreeMap<String, String>,
+        root: Option<&Path>,
+    ) -> Crate {
+        let mut git = GitSource::from(&self.source);
+        if let Some(git) = &mut git
+            && let Some(rev) = self.rev.as_ref().or(Some(&git.commit))
+        {
+            git.reference = GitReference::Rev(rev.clone());
+        }
+        Crate {
+            name: name.to_string(),
+            kind: self.kind(),
+            installed: self.version.clone(),
+            registry: Registry::from(&self.source, registries),
+            git,
+            source: self.source.clone(),
+            version_req: self.version_req.clone(),
+            bins: self.bins.clone(),
+            features: self.features.clone(),
+            all_features: self.all_features,
+            no_default_features: self.no_default_features,
+            profile: self.profile.clone(),
+            target: self.target.clone(),
+            root: root.map(Path::to_path_buf),
+            ..Default::default()
+        }
+    }
+}
+
+/// Action to sync an installed crate with a [`Manifest`]
+#[derive(Debug, Serialize, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum Action {
+    /// Crate is in the manifest but not installed
+    Install,
+
+    /// Installed crate differs from the manifest
+    Reinstall,
+
+    /// Installed crate is not in the manifest
+    Uninstall,
+}
+
+/// Step of the plan to sync the installed crates with a [`Manifest`] (see [`Manifest::plan`])
+#[derive(Debug, Serialize, Clone)]
+pub struct SyncStep {
+    pub name: String,
+    pub kind: Kind,
+    pub action: Action,
+
+    /// Installed version (`None` if not installed)
+    pub installed: Option<String>,
+
+    /// Version in the manifest (`None` if not in the manifest)
+    pub manifest: Option<String>,
+
+    /// Names of the fields that differ from the manifest (see [`ManifestCrate::differences`])
+    pub differences: Vec<&'static str>,
+
+    /// Cargo command to run
+    pub command: Vec<String>,
+}
+
+/// Crate that differs between two [`Manifest`]s (see [`Manifest::diff`])
+#[derive(Debug, Serialize, Clone)]
+pub struct Difference {
+    pub name: String,
+
+    /// Crate in the left manifest (`None` if only in the right one)
+    pub left: Option<ManifestCrate>,
+
+    /// Crate in the right manifest (`None` if only in the left one)
+    pub right: Option<ManifestCrate>,
+
+    /// Names of the fields that differ (empty if only in one manifest)
+    pub fields: Vec<&'static str>,
+}
+
+/// Crates that failed to process are skipped
+impl From<&Crates> for Manifest {
+    fn from(crates: &Crates) -> Manifest {
+        Manifest {
+            version: MANIFEST_VERSION,
+            crates: crates
+                .installs
+                .values()
+                .filter(|c| c.error.is_none())
+                .map(|c| {
+                    (
+                        c.name.clone(),
+                        ManifestCrate {
+                            version: c.installed.clone(),
+                            version_req: c.version_req.clone(),
+                            source: c.source.clone(),
+                            rev: c.git.as_ref().map(|x| x.commit.clone()),
+                            features: c.features.clone(),
+                            all_features: c.all_features,
+                            no_default_features: c.no_default_features,
+                            bins: c.bins.clone(),
+                            profile: c.profile.clone(),
+                            target: c.target.clone(),
+                            rustc: c.rustc.clone(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Manifest {
+    /**
+    Read a manifest from a JSON (`.json` extension) or TOML file
+
+    # Errors
+
+    Returns an error if not able to read or parse the file or it has an unsupported version
+    */
+    pub fn from_path(path: &Path) -> Result<Manifest, Error> {
+        let text = std::fs::read_to_string(path).map_err(|source| {
+            if source.kind() == std::io::ErrorKind::NotFound {
+                Error::NotFound {
+                    path: path.to_path_buf(),
+                }
+            } else {
+                Error::Read {
+                    path: path.to_path_buf(),
+                    source,
+                }
+            }
+        })?;
+        let manifest: Manifest = if path.extension().is_some_and(|x| x == "json") {
+            serde_json::from_str(&text).map_err(|source| Error::Parse {
+                path: path.to_path_buf(),
+                source,
+            })?
+        } else {
+            toml::from_str(&text).map_err(|source| Error::InvalidManifest {
+                path: path.to_path_buf(),
+                source,
+            })?
+        };
+        if manifest.version > MANIFEST_VERSION {
+            return Err(Error::UnsupportedManifest {
+                path: path.to_path_buf(),
+                version: manifest.version,
+            });
+        }
+        Ok(manifest)
+    }
+
+    /**
+    Read a manifest or a cargo install metadata file (a JSON file with an `installs` object)
+
+    # Errors
+
+    Returns an error if not able to read or parse the file
+    */
+    pub fn load(path: &Path) -> Result<Manifest, Error> {
+        let is_metadata = path.extension().is_some_and(|x| x == "json")
+            && std::fs::read_to_string(path)
+                .ok()
+                .and_then(|x| serde_json::from_str::<serde_json::Value>(&x).ok())
+                .is_some_and(|x| x.get("installs").is_some());
+        if is_metadata {
+            Ok(Manifest::from(&Crates::parse(
+                path,
+                &[],
+                &Options::default(),
+            )?))
+        } else {
+            Manifest::from_path(path)
+        }
+    }
+
+    /**
+    Compare with another manifest: crates only in one of them and crates whose version, source,
+    features, bins, profile, target, or rustc version differ
+    */
+    #[must_use]
+    pub fn diff(&self, other: &Manifest) -> Vec<Difference> {
+        let names = self
+            .crates
+            .keys()
+            .chain(other.crates.keys())
+            .collect::<std::collections::BTreeSet<_>>();
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let left = self.crates.get(name);
+                let right = other.crates.get(name);
+                let fields = match (left, right) {
+                    (Some(left), Some(right)) => {
+                        let mut fields = left.differences(right);
+                        if !left.rustc.is_empty()
+                            && !right.rustc.is_empty()
+                            && left.rustc != right.rustc
+                        {
+                            fields.push("rustc");
+                        }
+                        if fields.is_empty() {
+                            return None;
+                        }
+                        fields
+                    }
+                    _ => vec![],
+                };
+                Some(Difference {
+                    name: name.clone(),
+                    left: left.cloned(),
+                    right: right.cloned(),
+                    fields,
+                })
+            })
+            .collect()
+    }
+
+    /**
+    Plan the steps to sync the installed crates with the manifest: install missing crates,
+    reinstall crates whose version, source, features, bins, profile, or target differ, and
+    optionally uninstall crates not in the manifest
+
+    Commands use the installation root of the installed crates (see [`Crates::root`]).
+
+    # Errors
+
+    Returns an error if not able to read or parse `$CARGO_HOME/config.toml`
+    */
+    pub fn plan(&self, installed: &Crates, uninstall: bool) -> Result<Vec<SyncStep>, Error> {
+        let current = Manifest::from(installed);
+        let root = installed.root.as_deref();
+        let mut commands = self.install_commands(root)?;
+        let mut r = vec![];
+        for (name, entry) in &self.crates {
+            let (action, installed, differences) = match current.crates.get(name) {
+                Some(x) => {
+                    let differences = entry.differences(x);
+                    if differences.is_empty() {
+                        continue;
+                    }
+                    (Action::Reinstall, Some(x.version.clone()), differences)
+                }
+                None => (Action::Install, None, vec![]),
+            };
+            let mut command = commands.remove(name).unwrap_or_default();
+            if action == Action::Reinstall {
+                command.insert(2, String::from("--force"));
+            }
+            r.push(SyncStep {
+                name: name.clone(),
+                kind: entry.kind(),
+                action,
+                installed,
+                manifest: Some(entry.version.clone()),
+                differences,
+                command,
+            });
+        }
+        if uninstall {
+            for (name, x) in &current.crates {
+                if !self.crates.contains_key(name) {
+                    r.push(SyncStep {
+                        name: name.clone(),
+                        kind: x.kind(),
+                        action: Action::Uninstall,
+                        installed: Some(x.version.clone()),
+                        manifest: None,
+                        differences: vec![],
+                        command: ["cargo", "uninstall"]
+                            .into_iter()
+                            .map(String::from)
+                            .chain(
+                                root.into_iter().flat_map(|x| {
+                                    [String::from("--root"), x.display().to_string()]
+                                }),
+                            )
+                            .chain([name.clone()])
+                            .collect(),
+                    });
+                }
+            }
+        }
+        Ok(r)
+    }
+
+    /**
+    Save as a snapshot in the given directory (a TOML file named by the current time in
+    milliseconds)
+
+    # Errors
+
+    Returns an error if not able to create the directory or write the file
+    */
+    pub fn save_snapshot(&self, dir: &Path) -> Result<PathBuf, Error> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create `{}`", dir.display()))?;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = dir.join(format!("{millis:015}.toml"));
+        std::fs::write(&path, self.to_toml()?)
+            .with_context(|| format!("Failed to write `{}`", path.display()))?;
+        Ok(path)
+    }
+
+    /**
+    Read the snapshots in the given directory, oldest first
+
+    # Errors
+
+    Returns an error if not able to read or parse a snapshot
+    */
+    pub fn snapshots(dir: &Path) -> Result<Vec<(PathBuf, Manifest)>, Error> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Ok(vec![]);
+        };
+        let mut paths = entries
+            .filter_map(|x| x.ok().map(|x| x.path()))
+            .filter(|x| x.extension().is_some_and(|x| x == "toml"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|path| Manifest::from_path(&path).map(|x| (path, x)))
+            .collect()
+    }
+
+    /**
+    Get the crates to roll back from the snapshots in the given directory: the given crate from the
+    latest snapshot containing it, or all crates from the latest snapshot (last update run)
+
+    Local crates are ignored since reinstalling them builds whatever source is currently at their
+    path, not the snapshotted version.
+
+    # Errors
+
+    Returns an error if not able to read or parse a snapshot or there is no matching snapshot
+    */
+    pub fn rollback(dir: &Path, name: Option<&str>) -> Result<Manifest, Error> {
+        let mut snapshots = Manifest::snapshots(dir)?.into_iter().map(|(_path, mut x)| {
+            x.crates.retain(|_, c| c.kind() != Local);
+            x
+        });
+        let manifest = match name {
+            Some(name) => snapshots.rev().find_map(|mut x| {
+                let entry = x.crates.remove(name)?;
+                x.crates = BTreeMap::from([(name.to_string(), entry)]);
+                Some(x)
+            }),
+            None => snapshots.next_back(),
+        };
+        manifest
+            .filter(|x| !x.crates.is_empty())
+            .ok_or_else(|| Error::NoSnapshot {
+                name: name.map(ToString::to_string),
+            })
+    }
+
+    /**
+    Serialize to TOML
+
+    # Errors
+
+    Returns an error if not able to serialize the manifest
+    */
+    pub fn to_toml(&self) -> Result<String, Error> {
+        Ok(toml::to_string(self).map_err(anyhow::Error::from)?)
+    }
+
+    /**
+    Serialize to pretty JSON
+
+    # Errors
+
+    Returns an error if not able to serialize the manifest
+    */
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self).map_err(anyhow::Error::from)?)
+    }
+
+    /**
+    Generate the cargo install command to install each crate at its exact version (or commit) in
+    the given installation root (`None` for the cargo home directory)
+
+    # Errors
+
+    Returns an error if not able to read or parse `$CARGO_HOME/config.toml` (to resolve the names of
+    alternative registries)
+    */
+    pub fn install_commands(
+        &self,
+        root: Option<&Path>,
+    ) -> Result<BTreeMap<String, Vec<String>>, Error> {
+        let registries = CargoConfig::load()?.registries();
+        Ok(self
+            .crates
+            .iter()
+            .map(|(name, entry)| {
+                let c = entry.to_crate(name, &registries, root);
+                let version = (c.kind == External).then(|| format!("={}", c.installed));
+                (name.clone(), c.install_command(version.as_deref()))
+            })
+            .collect())
+    }
+
+    /**
+    Render as a shell script of cargo install commands (see [`Manifest::install_commands`])
+
+    # Errors
+
+    Returns an error if not able to read or parse `$CARGO_HOME/config.toml`
+    */
+    pub fn to_sh(&self) -> Result<String, Error> {
+        let mut r = vec![format!(
+            "#!/bin/sh\n# Generated by cargo-list (manifest version {})\nset -e",
+            self.version,
+        )];
+        for (name, command) in self.install_commands(None)? {
+            let command = command
+                .iter()
+                .map(|x| shell_quote(x))
+                .collect::<Vec<_>>()
+                .join(" ");
+            r.push(format!("# {name}\n{command}"));
+        }
+        Ok(r.join("\n\n") + "\n")
+    }
+}
+
+/// Quote an argument for a POSIX shell if needed
+fn shell_quote(s: &str) -> String {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_=.,/:+@%".contains(c))
+    {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+
+/// Number of lines of the output of a job to keep in [`JobResult::tail`]
+const TAIL: usize = 10;
+
+/// Command to install, update, or uninstall a crate (see [`Runner`])
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub name: String,
+    pub kind: Kind,
+    pub command: Vec<String>,
+}
+
+/// Status of a [`Job`] after running it
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
+pub enum Status {
+    /// Command succeeded
+    Ok,
+
+    /// Command failed (or could not be run)
+    Failed,
+
+    /// Command was not run after another job failed (see [`Runner::fail_fast`])
+    Skipped,
+}
+
+/// Result of running a [`Job`]
+#[derive(Debug, Clone)]
+pub struct JobResult {
+    pub name: String,
+    pub kind: Kind,
+    pub status: Status,
+    pub duration: Duration,
+
+    /// Log file with the output (`None` unless run with [`Runner::log_dir`])
+    pub log: Option<PathBuf>,
+
+    /// Last lines of the output (of stderr only unless logged)
+    pub tail: Vec<String>,
+}
+
+impl JobResult {
+    fn new(job: &Job, status: Status) -> JobResult {
+        JobResult {
+            name: job.name.clone(),
+            kind: job.kind.clone(),
+            status,
+            duration: Duration::ZERO,
+            log: None,
+            tail: vec![],
+        }
+    }
+
+    /// Return true if the job failed
+    #[must_use]
+    pub fn failed(&self) -> bool {
+        self.status == Status::Failed
+    }
+}
 
-Returns an error if not able to get the versions via the REST API
+/// Progress of [`Runner::run`]
+#[derive(Debug)]
+pub enum Event<'a> {
+    /// Job is about to run
+    Start(&'a Job),
+
+    /// Job finished or was skipped
+    Finish(&'a JobResult),
+}
+
+/// Runner of [`Job`]s, one or more at a time
+#[derive(Debug, Default, Clone)]
+pub struct Runner {
+    /// Number of jobs to run at a time (one if zero)
+    pub jobs: usize,
+
+    /// Skip the jobs that have not started yet after a job fails
+    pub fail_fast: bool,
+
+    /**
+    Directory to save the output of each job to (as `{name}.log`); `None` passes stdout through and
+    echoes stderr while keeping its last lines
+    */
+    pub log_dir: Option<PathBuf>,
+
+    /**
+    Target directory shared by all jobs; otherwise, each logged job builds in its own temporary
+    target directory (`{log_dir}/{name}`, removed after the job) so that concurrent jobs do not
+    block each other
+    */
+    pub target_dir: Option<PathBuf>,
+}
+
+impl Runner {
+    /**
+    Run the jobs and append their results to `results` in the order of the jobs
+
+    `progress` is called before each job runs and after it finishes or is skipped.
+    With [`Runner::fail_fast`], a failure already in `results` skips all of the jobs.
+
+    # Errors
+
+    Returns an error if not able to create the log directory or a log file
+    */
+    pub fn run(
+        &self,
+        jobs: &[Job],
+        results: &mut Vec<JobResult>,
+        progress: impl Fn(Event) + Sync,
+    ) -> Result<(), Error> {
+        if let Some(dir) = &self.log_dir {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create `{}`", dir.display()))?;
+        }
+        let stop = std::sync::atomic::AtomicBool::new(
+            self.fail_fast && results.iter().any(JobResult::failed),
+        );
+        let run = |job: &Job| {
+            let result = if stop.load(std::sync::atomic::Ordering::Relaxed) {
+                JobResult::new(job, Status::Skipped)
+            } else {
+                progress(Event::Start(job));
+                let result = self.run_job(job)?;
+                if result.failed() && self.fail_fast {
+                    stop.store(true, std::sync::atomic::Ordering::Relaxed);
+                }
+                result
+            };
+            progress(Event::Finish(&result));
+            Ok(result)
+        };
+        if self.jobs > 1 {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(self.jobs)
+                .build()
+                .map_err(anyhow::Error::from)?;
+            results.extend(
+                pool.install(|| jobs.par_iter().map(run).collect::<Result<Vec<_>, Error>>())?,
+            );
+        } else {
+            for job in jobs {
+                results.push(run(job)?);
+            }
+        }
+        Ok(())
+    }
+
+    /**
+    Run a job
+
+    The target directory is given to `cargo install` via `--target-dir` (which overrides
+    `build.target-dir` in cargo's configuration and the workspace target directory of `--path`
+    installs) and to other commands via `CARGO_TARGET_DIR`.
+    */
+    fn run_job(&self, job: &Job) -> Result<JobResult, Error> {
+        let mut result = JobResult::new(job, Status::Failed);
+        let temporary = self
+            .log_dir
+            .as_ref()
+            .filter(|_| self.target_dir.is_none())
+            .map(|x| x.join(&job.name));
+        let target_dir = self.target_dir.as_ref().or(temporary.as_ref());
+        let mut args = job.command[1..].to_vec();
+        let mut command = std::process::Command::new(&job.command[0]);
+        if let Some(target_dir) = target_dir {
+            if args.first().is_some_and(|x| x == "install") {
+                args.splice(
+                    1..1,
+                    [
+                        String::from("--target-dir"),
+                        target_dir.display().to_string(),
+                    ],
+                );
+            } else {
+                command.env("CARGO_TARGET_DIR", target_dir);
+            }
+        }
+        command.args(&args).stdin(std::process::Stdio::null());
+
+        let start = Instant::now();
+        let mut tail = std::collections::VecDeque::with_capacity(TAIL);
+        let status = if let Some(dir) = &self.log_dir {
+            let log = dir.join(format!("{}.log", job.name));
+            let file = File::create(&log)
+                .with_context(|| format!("Failed to create `{}`", log.display()))?;
+            let status = file
+                .try_clone()
+                .and_then(|x| command.stdout(x).stderr(file).status());
+            if let Err(e) = &status {
+                std::fs::write(&log, format!("Failed to run `{}`: {e}\n", job.command[0]))
+                    .with_context(|| format!("Failed to write `{}`", log.display()))?;
+            }
+            for line in String::from_utf8_lossy(&std::fs::read(&log).unwrap_or_default()).lines() {
+                keep(&mut tail, line);
+            }
+            result.log = Some(log);
+            status
+        } else {
+            command.stderr(std::process::Stdio::piped());
+            command.spawn().and_then(|mut child| {
+                if let Some(stderr) = child.stderr.take() {
+                    let stderr = std::io::BufReader::new(stderr);
+                    for line in std::io::BufRead::split(stderr, b'\n') {
+                        let line = String::from_utf8_lossy(&line?).into_owned();
+                        eprintln!("{line}");
+                        keep(&mut tail, &line);
+                    }
+                }
+                child.wait()
+            })
+        };
+        result.duration = start.elapsed();
+        if let Some(dir) = &temporary {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+        match status {
+            Ok(status) if status.success() => result.status = Status::Ok,
+            Ok(_) => {}
+            Err(e) => {
+                if result.log.is_none() {
+                    keep(
+                        &mut tail,
+                        &format!("Failed to run `{}`: {e}", job.command[0]),
+                    );
+                }
+            }
+        }
+        result.tail = tail.into();
+        Ok(result)
+    }
+
+    /**
+    Check the results of [`Runner::run`]
+
+    # Errors
+
+    Returns an error with the number of failed jobs if any failed
+    */
+    pub fn check(results: &[JobResult]) -> Result<(), Error> {
+        match results.iter().filter(|x| x.failed()).count() {
+            0 => Ok(()),
+            failed => Err(Error::Failed { failed }),
+        }
+    }
+}
+
+/// Keep a line in the tail of the output of a job
+fn keep(tail: &mut std::collections::VecDeque<String>, line: &str) {
+    if tail.len() == TAIL {
+        tail.pop_front();
+    }
+    tail.push_back(line.trim_end().to_string());
+}
+
+//--------------------------------------------------------------------------------------------------
+
+/**
+Deserialize the crate version object returned via the crates.io API
+(`https://crates.io/api/v1/crates/{name}/versions`) in the [`latest()`] function or built from the
+entries of the sparse registry index or the local registry index cache
 */
-pub fn latest(
-    name: &str,
-    version_req: &Option<String>,
-    prerelease: bool,
-) -> Result<(String, Vec<String>)> {
-    let url = format!("https://crates.io/api/v1/crates/{name}/versions");
-    let res = CLIENT.get(&url).send()?;
-    let res = res.error_for_status()?;
-    let versions = res.json::<Versions>()?;
-    let available = versions.available(prerelease);
-    if let Some(req_str) = version_req {
-        let req = semver::VersionReq::parse(req_str)?;
+#[derive(Debug, Deserialize)]
+struct Versions {
+    versions: Vec<Version>,
+
+    /// Versions were read from the local registry index cache and may be stale
+    #[serde(skip)]
+    stale: bool,
+}
+
+impl Versions {
+    /**
+    Get the versions of a crate via the sparse registry index, the crates.io API, or the local
+    registry index cache
+
+    Crates from alternative registries are resolved via their sparse or git index, or the local
+    registry index cache if offline.
+    */
+    fn fetch(name: &str, registry: Option<&Registry>, resolver: &Resolver) -> Result<Versions> {
+        let options = &resolver.options;
+        let Some(registry) = registry else {
+            if options.offline {
+                return Versions::from_index_cache(name, &CRATES_IO_CACHE_PREFIXES);
+            }
+            return match options.backend {
+                Backend::Index => {
+                    let base = options
+                        .registry_url
+                        .as_deref()
+                        .unwrap_or(CRATES_IO_SPARSE_INDEX);
+                    let url = format!(
+                        "{}/{}",
+                        base.trim_start_matches("sparse+").trim_end_matches('/'),
+                        index_path(name),
+                    );
+                    Versions::from_index(&get(&url, None, name, resolver)?)
+                }
+                Backend::Api => {
+                    let base = options.registry_url.as_deref().unwrap_or(CRATES_IO_API);
+                    let url = format!("{}/crates/{name}/versions", base.trim_end_matches('/'));
+                    Ok(serde_json::from_str(&get(&url, None, name, resolver)?)?)
+                }
+            };
+        };
+        if options.offline {
+            let prefix = registry.cache_prefix().ok_or_else(|| {
+                anyhow!(
+                    "Failed to parse the registry index URL `{}`",
+                    registry.index
+                )
+            })?;
+            Versions::from_index_cache(name, &[prefix.as_str()])
+        } else if let Some(url) = registry.index.strip_prefix("sparse+") {
+            let url = format!("{}/{}", url.trim_end_matches('/'), index_path(name));
+            Versions::from_index(&get(&url, registry.token(), name, resolver)?)
+        } else {
+            let dir = resolver.git_index(registry)?;
+            let file = git(&dir, &["show", &format!("FETCH_HEAD:{}", index_path(name))])
+                .with_context(|| format!("Failed to find `{name}` in `{}`", registry.index))?;
+            Versions::from_index(&file)
+        }
+    }
+
+    /// Parse a registry index file (one JSON entry per line)
+    fn from_index(text: &str) -> Result<Versions> {
+        Ok(Versions::from(
+            text.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str::<Version>)
+                .collect::<serde_json::Result<Vec<_>>>()?,
+        ))
+    }
+
+    /**
+    Read the versions of a crate from the most recently updated registry index cache under
+    `$CARGO_HOME/registry/index` whose directory name starts with one of the given prefixes
+    */
+    fn from_index_cache(name: &str, prefixes: &[&str]) -> Result<Versions> {
+        let index = cargo_home().join("registry").join("index");
+        let rel = index_path(name);
+        let path = std::fs::read_dir(&index)
+            .with_context(|| {
+                format!(
+                    "Failed to read the registry index directory `{}`",
+                    index.display()
+                )
+            })?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let dir = entry.file_name().into_string().ok()?;
+                if !prefixes.iter().any(|prefix| dir.starts_with(prefix)) {
+                    return None;
+                }
+                let path = entry.path().join(".cache").join(&rel);
+                let modified = path.metadata().and_then(|x| x.modified()).ok()?;
+                Some((modified, path))
+            })
+            .max_by_key(|(modified, _path)| *modified)
+            .map(|(_modified, path)| path)
+            .ok_or_else(|| anyhow!("Failed to find `{name}` in the local registry index cache"))?;
+        let mut versions =
+            Versions::from_index_cache_file(&std::fs::read(&path)?).with_context(|| {
+                format!(
+                    "Failed to parse the registry index cache file `{}`",
+                    path.display(),
+                )
+            })?;
+        versions.stale = true;
+        Ok(versions)
+    }
+
+    /**
+    Parse a cargo registry index cache file
+
+    The format is a cache version byte, the index format version (`u32`, little endian), the index
+    revision, and then pairs of version and JSON index entry; all strings are NUL-terminated.
+    */
+    fn from_index_cache_file(data: &[u8]) -> Result<Versions> {
+        const CACHE_VERSION: u8 = 3;
+        let (&cache_version, rest) = data
+            .split_first()
+            .ok_or_else(|| anyhow!("Empty cache file"))?;
+        if cache_version != CACHE_VERSION || rest.len() < 4 {
+            return Err(anyhow!("Unsupported cache version {cache_version}"));
+        }
+        let mut fields = rest[4..].split(|&b| b == 0).skip(1);
+        let mut versions = vec![];
+        while let (Some(_version), Some(entry)) = (fields.next(), fields.next()) {
+            versions.push(serde_json::from_slice::<Version>(entry)?);
+        }
+        Ok(Versions::from(versions))
+    }
+
+    fn iter(&self) -> std::slice::Iter<'_, Version> {
+        self.versions.iter()
+    }
+
+    fn available(&self, prerelease: bool) -> Vec<&Version> {
+        self.iter().filter(|x| x.is_available(prerelease)).collect()
+    }
+
+    /// Return true if the given version was yanked
+    fn is_yanked(&self, version: &semver::Version) -> bool {
+        self.iter().any(|x| x.num == *version && x.yanked)
+    }
+
+    /**
+    Get the nearest available version to the given (yanked) version: the newest semver-compatible
+    version, else the newest older version, else the newest version
+    */
+    fn nearest(&self, version: &semver::Version, prerelease: bool) -> Option<String> {
+        let available = self.available(prerelease);
+        let req = semver::VersionReq::parse(&format!("^{version}")).ok();
+        available
+            .iter()
+            .find(|x| req.as_ref().is_some_and(|req| req.matches(&x.num)))
+            .or_else(|| available.iter().find(|x| x.num < *version))
+            .or(available.first())
+            .map(|x| x.num.to_string())
+    }
+
+    /**
+    Get the latest available version of a crate, optionally matching a required version, any newer
+    versions that do not match it, and the newest matching version compatible with the given Rust
+    version
+
+    # Errors
+
+    Returns an error if the requirement is invalid or no available version matches it
+    */
+    fn latest(
+        &self,
+        name: &str,
+        version_req: Option<&str>,
+        prerelease: bool,
+        rust_version: Option<&str>,
+    ) -> Result<Latest, Error> {
+        let available = self.available(prerelease);
+        let req = version_req
+            .map(|req_str| {
+                semver::VersionReq::parse(req_str).map_err(|source| Error::InvalidRequirement {
+                    name: name.to_string(),
+                    requirement: req_str.to_string(),
+                    source,
+                })
+            })
+            .transpose()?;
+        let matches = |v: &Version| req.as_ref().is_none_or(|req| req.matches(&v.num));
+        let rust_version = rust_version.and_then(parse_rust_version);
         let mut newer = vec![];
-        for v in &available {
-            if req.matches(&v.num) {
-                return Ok((v.num.to_string(), newer));
+        for (i, v) in available.iter().enumerate() {
+            if matches(v) {
+                return Ok(Latest {
+                    version: v.num.to_string(),
+                    newer,
+                    compatible: available[i..]
+                        .iter()
+                        .find(|x| matches(x) && x.is_compatible(rust_version))
+                        .map(|x| x.num.to_string()),
+                });
             }
             newer.push(v.num.to_string());
         }
@@ -392,48 +3038,375 @@ pub fn latest(
         // If we haven't found a match yet, but we are allowing prereleases,
         // it's possible the requirement string didn't explicitly opt-in to prereleases (like
         // `^2.0.0`) but the available versions are prereleases (like `2.0.0-rc.37`).
-        // In this specific case, if we found *no* matching versions, we might want to be lenient,
-        // but semver::VersionReq is strict.
-        // However, if the user INSTALLED a prerelease, usually the version_req in `.crates2.json`
-        // reflects that (e.g. it might be `=2.0.0-rc.37` or `^2.0.0-rc.37`).
+        // In this specific case, if we found *no* matching versions, we might want to be
+        // lenient, but semver::VersionReq is strict.
+        // However, if the user INSTALLED a prerelease, usually the version_req in
+        // `.crates2.json` reflects that (e.g. it might be `=2.0.0-rc.37` or `^2.0.0-rc.37`).
         // If the error persists, it means even with prereleases included in `available`, none
         // matched `req`.
 
-        Err(anyhow!(
-            "\
-                Failed to find an available version matching the requirement `{req_str}` \
-                (available: {:?})\
-            ",
-            available
-                .iter()
-                .take(5)
-                .map(|v| v.num.to_string())
-                .collect::<Vec<_>>()
-        ))
-    } else if available.is_empty() {
-        Err(anyhow!("Failed to find any available version"))
+        Err(Error::NoMatchingVersion {
+            name: name.to_string(),
+            requirement: version_req.map(ToString::to_string),
+        })
+    }
+}
+
+impl From<Vec<Version>> for Versions {
+    /// Sort versions from newest to oldest like the crates.io API
+    fn from(mut versions: Vec<Version>) -> Versions {
+        versions.sort_by(|a, b| b.num.cmp(&a.num));
+        Versions {
+            versions,
+            stale: false,
+        }
+    }
+}
+
+/// Crate version from the crates.io API (`num`) or a registry index entry (`vers`)
+#[derive(Debug, Deserialize)]
+struct Version {
+    #[serde(alias = "vers")]
+    num: semver::Version,
+    yanked: bool,
+
+    /// Minimum supported Rust version (`rust-version` in the crate's `Cargo.toml`)
+    #[serde(default, rename = "rust_version")]
+    msrv: Option<String>,
+}
+
+impl Version {
+    fn is_available(&self, prerelease: bool) -> bool {
+        if !prerelease && !self.num.pre.is_empty() {
+            return false;
+        }
+        !self.yanked
+    }
+
+    /// Return true if the given Rust version (or an unknown one) is able to build this version
+    fn is_compatible(&self, rust_version: Option<(u64, u64, u64)>) -> bool {
+        rust_version
+            .zip(self.msrv.as_deref().and_then(parse_rust_version))
+            .is_none_or(|(active, required)| active >= required)
+    }
+}
+
+/// Parse a Rust version like `1.85`, `1.85.0`, or `1.87.0-nightly` ignoring any prerelease
+fn parse_rust_version(s: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = s.split(['-', '+']).next()?.split('.').map(str::parse);
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().transpose().ok()?.unwrap_or_default();
+    let patch = parts.next().transpose().ok()?.unwrap_or_default();
+    Some((major, minor, patch))
+}
+
+/// Latest available version of a crate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Latest {
+    /// Latest available version matching the requirement (if any)
+    pub version: String,
+
+    /// Newer available versions that do not match the requirement
+    pub newer: Vec<String>,
+
+    /**
+    Newest available version matching the requirement whose `rust-version` the given Rust version
+    satisfies (`None` if none)
+    */
+    pub compatible: Option<String>,
+}
+
+//--------------------------------------------------------------------------------------------------
+
+/**
+Get the latest available (not prerelease or yanked) version(s) for a crate, optionally matching a
+required version, and the newest one compatible with the given Rust version (all versions are
+compatible if `None`)
+
+# Errors
+
+Returns an [`Error::InvalidName`] if the name is not a valid crate name, an [`Error::Registry`] if
+not able to get the versions via the sparse registry index, or an [`Error::NoMatchingVersion`] if
+no available version matches the requirement
+*/
+pub fn latest(
+    name: &str,
+    version_req: &Option<String>,
+    prerelease: bool,
+    rust_version: Option<&str>,
+) -> Result<Latest, Error> {
+    if !is_valid_name(name) {
+        return Err(Error::InvalidName {
+            name: name.to_string(),
+        });
+    }
+    let resolver = Resolver::new(&Options::default())?;
+    Versions::fetch(name, None, &resolver)
+        .map_err(|source| Error::Registry {
+            name: name.to_string(),
+            source,
+        })?
+        .latest(name, version_req.as_deref(), prerelease, rust_version)
+}
+
+/// Cached registry response
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+
+    /// Seconds since the Unix epoch when the response was last fetched or revalidated
+    fetched: u64,
+
+    body: String,
+}
+
+impl CacheEntry {
+    /// Path of the cache file for a crate's response from the given URL
+    fn path(cache_dir: &Path, url: &str, name: &str) -> PathBuf {
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|x| x.host_str().map(ToString::to_string))
+            .unwrap_or_default();
+        cache_dir.join(host).join(format!("{name}.json"))
+    }
+
+    /// Load the cache entry for the given URL, if any
+    fn load(path: &Path, url: &str) -> Option<CacheEntry> {
+        let entry = serde_json::from_reader::<_, CacheEntry>(File::open(path).ok()?).ok()?;
+        (entry.url == url).then_some(entry)
+    }
+
+    /// Save the cache entry; failures are ignored since the cache is only an optimization
+    fn save(&self, path: &Path) {
+        let Some(dir) = path.parent() else {
+            return;
+        };
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        if std::fs::create_dir_all(dir).is_ok()
+            && std::fs::write(&tmp, serde_json::to_vec(self).unwrap_or_default()).is_ok()
+        {
+            let _ = std::fs::rename(&tmp, path);
+        }
+    }
+
+    /// Return true if the entry is younger than the given TTL
+    fn is_fresh(&self, ttl: Duration) -> bool {
+        now().saturating_sub(self.fetched) < ttl.as_secs()
+    }
+}
+
+/// Seconds since the Unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs())
+}
+
+/**
+Get the body of a registry response for a crate, using and updating the on-disk cache per the
+[`Options`]; `file://` URLs are read directly
+
+# Errors
+
+Returns an error if the request fails or the response status is an error
+*/
+fn get(url: &str, token: Option<String>, name: &str, resolver: &Resolver) -> Result<String> {
+    use reqwest::{Url, header};
+
+    let options = &resolver.options;
+    if url.starts_with("file://") {
+        let path = Url::parse(url)?
+            .to_file_path()
+            .map_err(|()| anyhow!("Invalid file URL `{url}`"))?;
+        return std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read `{}`", path.display()));
+    }
+
+    let path = options
+        .cache_dir
+        .as_ref()
+        .map(|dir| CacheEntry::path(dir, url, name));
+    let cached = path
+        .as_ref()
+        .filter(|_path| !options.refresh)
+        .and_then(|path| CacheEntry::load(path, url));
+
+    if let Some(entry) = &cached
+        && entry.is_fresh(options.cache_ttl)
+    {
+        return Ok(entry.body.clone());
+    }
+
+    let mut req = CLIENT.get(url);
+    if let Some(token) = token {
+        req = req.header(header::AUTHORIZATION, token);
+    }
+    if let Some(entry) = &cached {
+        if let Some(etag) = &entry.etag {
+            req = req.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            req = req.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let res = resolver.send(&req)?;
+
+    let entry = if res.status() == StatusCode::NOT_MODIFIED
+        && let Some(mut entry) = cached
+    {
+        entry.fetched = now();
+        entry
     } else {
-        Ok((available[0].num.to_string(), vec![]))
+        let res = res.error_for_status()?;
+        let value = |name| {
+            res.headers()
+                .get(name)
+                .and_then(|x| x.to_str().ok())
+                .map(ToString::to_string)
+        };
+        CacheEntry {
+            url: url.to_string(),
+            etag: value(header::ETAG),
+            last_modified: value(header::LAST_MODIFIED),
+            fetched: now(),
+            body: res.text()?,
+        }
+    };
+
+    if let Some(path) = &path {
+        entry.save(path);
+    }
+    Ok(entry.body)
+}
+
+/**
+Run git in the given repository without prompting for credentials and return its stdout
+
+# Errors
+
+Returns an error if git fails to run or exits with an error
+*/
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .context("Failed to run `git`")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Return true if the name is non-empty and only contains ASCII alphanumerics, `-`, and `_`
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/**
+Get the path of a crate's file relative to the root of a registry index (the name must be valid per
+[`is_valid_name`])
+
+See <https://doc.rust-lang.org/cargo/reference/registry-index.html#index-files>.
+*/
+fn index_path(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{name}"),
+        2 => format!("2/{name}"),
+        3 => format!("3/{}/{name}", &name[..1]),
+        _ => format!("{}/{}/{name}", &name[..2], &name[2..4]),
     }
 }
 
-/// Get the active toolchain
+/**
+Get the active toolchain via rustup, falling back to the rustc version if rustup is not available
+
+Returns an empty string if neither is available.
+*/
 #[must_use]
 pub fn active_toolchain() -> String {
-    let r = Shell {
-        print: false,
-        ..Default::default()
-    }
-    .run(&[Command {
-        command: String::from("rustup show active-toolchain -v"),
-        stdout: Pipe::string(),
-        ..Default::default()
-    }]);
-    if let Pipe::String(Some(s)) = &r[0].stdout {
-        s.clone()
-    } else {
-        String::new()
-    }
+    [
+        ("rustup", &["show", "active-toolchain", "-v"][..]),
+        ("rustc", &["-V"][..]),
+    ]
+    .iter()
+    .find_map(|(program, args)| {
+        std::process::Command::new(program)
+            .args(*args)
+            .output()
+            .ok()
+            .filter(|x| x.status.success())
+            .and_then(|x| String::from_utf8(x.stdout).ok())
+    })
+    .unwrap_or_default()
+}
+
+/**
+Get the cargo home directory (`$CARGO_HOME` or `~/.cargo`)
+
+# Panics
+
+Panics if `$CARGO_HOME` is unset and not able to get the user's home directory
+*/
+#[must_use]
+pub fn cargo_home() -> PathBuf {
+    std::env::var_os("CARGO_HOME").map_or_else(|| expanduser("~/.cargo"), PathBuf::from)
+}
+
+/**
+Get the default location of the [RustSec](https://rustsec.org) advisory database
+(`$CARGO_HOME/advisory-db`)
+*/
+#[must_use]
+pub fn advisory_db() -> PathBuf {
+    cargo_home().join("advisory-db")
+}
+
+/**
+Get the path of the configuration file (`$CARGO_LIST_CONFIG` or, for example,
+`~/.config/cargo-list/config.toml` on Linux)
+*/
+#[must_use]
+pub fn config_path() -> Option<PathBuf> {
+    std::env::var_os("CARGO_LIST_CONFIG")
+        .map(PathBuf::from)
+        .or_else(|| dirs::config_dir().map(|x| x.join("cargo-list").join("config.toml")))
+}
+
+/**
+Get the default directory to save snapshots of crates before updating them (for example,
+`~/.local/share/cargo-list/snapshots` on Linux)
+*/
+#[must_use]
+pub fn snapshot_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|x| x.join("cargo-list").join("snapshots"))
+}
+
+/**
+Get the default directory to save the logs of concurrent updates (for example,
+`~/.local/share/cargo-list/logs` on Linux)
+*/
+#[must_use]
+pub fn log_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|x| x.join("cargo-list").join("logs"))
+}
+
+/// Get the default cache directory (for example, `~/.cache/cargo-list` on Linux)
+#[must_use]
+pub fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|x| x.join("cargo-list"))
 }
 
 /**
@@ -0,0 +1,118 @@
+//! Helpers shared by the integration tests
+
+#![allow(dead_code)]
+
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Create an empty temporary directory unique to the test and process
+pub fn temp_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cargo-list-{test}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Render a registry index file (one JSON entry per line) for the given versions
+pub fn index_file(name: &str, versions: &[(&str, bool)]) -> String {
+    versions
+        .iter()
+        .map(|(version, yanked)| index_entry(name, version, *yanked) + "\n")
+        .collect()
+}
+
+fn index_entry(name: &str, version: &str, yanked: bool) -> String {
+    format!(r#"{{"name":"{name}","vers":"{version}","yanked":{yanked}}}"#)
+}
+
+/// Write a registry index cache file for a crate under the given registry index directory
+pub fn write_index_cache(dir: &Path, name: &str, versions: &[(&str, bool)]) {
+    let path = dir.join(".cache").join(&name[..2]).join(&name[2..4]);
+    std::fs::create_dir_all(&path).unwrap();
+
+    // Cache version, index format version, revision, then version/entry pairs
+    let mut data = vec![3, 2, 0, 0, 0];
+    data.extend(b"etag: test\0");
+    for (version, yanked) in versions {
+        data.extend(version.as_bytes());
+        data.push(0);
+        data.extend(index_entry(name, version, *yanked).bytes());
+        data.push(0);
+    }
+    std::fs::write(path.join(name), data).unwrap();
+}
+
+/// Write a `.crates2.json` file with the given keys (`name version (source)`)
+pub fn write_crates2(path: &Path, keys: &[&str]) {
+    let installs = keys
+        .iter()
+        .map(|key| {
+            format!(
+                r#""{key}":{{"version_req":null,"bins":["{}"],"features":[],
+                "all_features":false,"no_default_features":false,"profile":"release",
+                "target":"x86_64-unknown-linux-gnu","rustc":"rustc 1.85.0 (4d91de4e4 2025-02-17)"}}"#,
+                key.split(' ').next().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, format!(r#"{{"installs":{{{installs}}}}}"#)).unwrap();
+}
+
+/**
+Serve the given files (by URL path) over HTTP on a local port with an `ETag` and support for
+`If-None-Match`, after answering the first `failures` requests with `429 Too Many Requests`; return
+the base URL and a log of the requests (request line plus lowercase headers)
+*/
+pub fn serve(
+    files: BTreeMap<String, String>,
+    failures: usize,
+) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(vec![]));
+    let log = requests.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+            let mut head = vec![];
+            for line in BufReader::new(&stream).lines() {
+                let line = line.unwrap();
+                if line.is_empty() {
+                    break;
+                }
+                head.push(line.to_lowercase());
+            }
+            let path = head[0].split(' ').nth(1).unwrap_or_default().to_string();
+            let mut log = log.lock().unwrap();
+            let response = if log.len() < failures {
+                String::from(
+                    "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nContent-Length: 0\r\n\r\n",
+                )
+            } else if let Some(body) = files.get(&path) {
+                let etag = format!("\"{}\"", body.len());
+                if head.iter().any(|x| *x == format!("if-none-match: {etag}")) {
+                    String::from("HTTP/1.1 304 Not Modified\r\nContent-Length: 0\r\n\r\n")
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nETag: {etag}\r\nContent-Length: {}\r\n\r\n{body}",
+                        body.len(),
+                    )
+                }
+            } else {
+                String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+            };
+            log.push(head.join("\n"));
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    (url, requests)
+}
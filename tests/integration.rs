@@ -1,7 +1,13 @@
+mod common;
+
 use {
-    cargo_list::{Crates, expanduser},
+    cargo_list::{
+        Action, Config, Crates, Error, Event, Installer, Job, Kind, MANIFEST_VERSION, Manifest,
+        Options, Policy, Runner, Status, Update, expanduser, latest, short_commit,
+    },
+    common::{index_file, serve, temp_dir, write_crates2},
     rayon::prelude::*,
-    std::collections::BTreeMap,
+    std::{collections::BTreeMap, sync::Mutex, time::Duration},
 };
 
 #[test]
@@ -97,3 +103,1349 @@ fn crates_from_include() {
         }
     }
 }
+
+#[test]
+fn crates_from_options_registry_url() {
+    let dir = temp_dir("registry-url");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &["foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)"],
+    );
+    let index = index_file("foobar", &[("1.0.0", false), ("1.1.0", false)]);
+
+    // Local stand-in server with the on-disk cache
+    let (url, requests) = serve(
+        BTreeMap::from([(String::from("/fo/ob/foobar"), index.clone())]),
+        0,
+    );
+    let mut options = Options {
+        registry_url: Some(url),
+        cache_dir: Some(dir.join("cache")),
+        ..Default::default()
+    };
+    let available = |options: &Options| {
+        Crates::from_options(&path, &[], options).unwrap().crates()["foobar"]
+            .available
+            .clone()
+    };
+    assert_eq!(available(&options), "1.1.0");
+    assert_eq!(available(&options), "1.1.0");
+    options.cache_ttl = Duration::from_hours(1);
+    assert_eq!(available(&options), "1.1.0");
+    {
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(!requests[0].contains("if-none-match"));
+        assert!(requests[1].contains("if-none-match"));
+    }
+
+    // Local directory with the sparse index layout
+    std::fs::create_dir_all(dir.join("index/fo/ob")).unwrap();
+    std::fs::write(dir.join("index/fo/ob/foobar"), index).unwrap();
+    let options = Options {
+        registry_url: Some(format!("file://{}/index/", dir.display())),
+        ..Default::default()
+    };
+    assert_eq!(available(&options), "1.1.0");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crates_from_options_retries() {
+    let dir = temp_dir("retries");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &["foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)"],
+    );
+    let files = BTreeMap::from([(
+        String::from("/fo/ob/foobar"),
+        index_file("foobar", &[("1.0.0", false), ("1.1.0", false)]),
+    )]);
+
+    for (retries, ok) in [(1, false), (2, true)] {
+        let (url, requests) = serve(files.clone(), 2);
+        let options = Options {
+            registry_url: Some(url),
+            retries,
+            timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let installed = Crates::from_options(&path, &[], &options).unwrap();
+        assert_eq!(installed.crates()["foobar"].error.is_none(), ok);
+        assert_eq!(requests.lock().unwrap().len(), retries as usize + 1);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crates_from_options_git() {
+    let dir = temp_dir("git");
+    let repo = dir.join("repo");
+    std::fs::create_dir_all(&repo).unwrap();
+    let git = |args: &[&str]| {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&repo)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    };
+    git(&["init", "-q", "-b", "main"]);
+    git(&["commit", "-q", "--allow-empty", "-m", "old"]);
+    git(&["tag", "v1"]);
+    let old = git(&["rev-parse", "HEAD"]);
+    git(&["commit", "-q", "--allow-empty", "-m", "new"]);
+    let new = git(&["rev-parse", "HEAD"]);
+
+    let path = dir.join(".crates2.json");
+    let url = format!("file://{}", repo.display());
+    write_crates2(
+        &path,
+        &[
+            &format!("branch 0.1.0 (git+{url}?branch=main#{old})"),
+            &format!("tag 0.1.0 (git+{url}?tag=v1#{old})"),
+            &format!("head 0.1.0 (git+{url}#{new})"),
+        ],
+    );
+    let options = Options {
+        git: true,
+        ..Default::default()
+    };
+    let installed = Crates::from_options(&path, &[], &options).unwrap();
+    let all = installed.crates();
+
+    let c = all["branch"];
+    assert!(c.outdated);
+    assert_eq!(c.git.as_ref().unwrap().remote_commit.as_ref(), Some(&new));
+    assert!(
+        c.update_command(false)
+            .join(" ")
+            .contains(&format!("--git {url} --branch main"))
+    );
+    assert!(!all["tag"].outdated);
+    assert!(!all["head"].outdated);
+    assert_eq!(short_commit(&new), &new[..7]);
+    assert_eq!(short_commit("ééééééééé"), "ééééééé");
+    assert_eq!(short_commit("abc"), "abc");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crates_from_options_git_index() {
+    let dir = temp_dir("git-index");
+    let index = dir.join("index");
+    std::fs::create_dir_all(index.join("fo/ob")).unwrap();
+    let git = |args: &[&str]| {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&index)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+    };
+    let publish = |versions: &[(&str, bool)]| {
+        std::fs::write(index.join("fo/ob/foobar"), index_file("foobar", versions)).unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "publish"]);
+    };
+    git(&["init", "-q", "-b", "main"]);
+    publish(&[("1.0.0", false), ("1.1.0", false)]);
+
+    let path = dir.join(".crates2.json");
+    let url = format!("file://{}", index.display());
+    write_crates2(
+        &path,
+        &[
+            &format!("foobar 1.0.0 (registry+{url})"),
+            &format!("missing 1.0.0 (registry+{url})"),
+        ],
+    );
+    let mut options = Options {
+        cache_dir: Some(dir.join("cache")),
+        cache_ttl: Duration::from_mins(5),
+        ..Default::default()
+    };
+    let available = |options: &Options| {
+        let installed = Crates::from_options(&path, &[], options).unwrap();
+        let all = installed.crates();
+        assert!(all["missing"].error.is_some());
+        let c = all["foobar"];
+        assert!(!c.stale);
+        assert!(
+            c.update_command(false)
+                .join(" ")
+                .contains(&format!("--index {url} "))
+        );
+        c.available.clone()
+    };
+    assert_eq!(available(&options), "1.1.0");
+
+    // Fetched again after the TTL or on refresh
+    publish(&[("1.0.0", false), ("1.1.0", false), ("1.2.0", false)]);
+    assert_eq!(available(&options), "1.1.0");
+    options.refresh = true;
+    assert_eq!(available(&options), "1.2.0");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crates_from_options_local() {
+    let dir = temp_dir("local");
+    let write = |path: &std::path::Path, contents: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    };
+    let changed = dir.join("changed");
+    write(
+        &changed.join("Cargo.toml"),
+        "[package]\nname = \"changed\"\nversion = \"0.2.0\"\n",
+    );
+    let workspace = dir.join("workspace");
+    write(
+        &workspace.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"member\"]\n\n[workspace.package]\nversion = \"1.0.0\"\n",
+    );
+    write(
+        &workspace.join("member/Cargo.toml"),
+        "[package]\nname = \"member\"\nversion.workspace = true\n",
+    );
+    write(&workspace.join("member/src/main.rs"), "fn main() {}\n");
+
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            &format!("changed 0.1.0 (path+file://{})", changed.display()),
+            &format!(
+                "member 1.0.0 (path+file://{})",
+                workspace.join("member").display()
+            ),
+        ],
+    );
+    let options = Options {
+        local: true,
+        ..Default::default()
+    };
+    let installed = Crates::from_options(&path, &[], &options).unwrap();
+    let all = installed.crates();
+
+    let c = all["changed"];
+    assert!(c.outdated);
+    assert_eq!(c.available, "0.2.0");
+    assert!(
+        c.update_command(false)
+            .join(" ")
+            .ends_with(&format!("--path {}", changed.display()))
+    );
+    assert!(!all["member"].outdated);
+    assert_eq!(all["member"].available, "1.0.0");
+
+    // Source modified after the binary was installed
+    write(&dir.join("bin/member"), "");
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    write(&workspace.join("member/src/main.rs"), "fn main() {}\n");
+    let options = Options {
+        local: true,
+        local_mtime: true,
+        ..Default::default()
+    };
+    let installed = Crates::from_options(&path, &[], &options).unwrap();
+    let c = installed.crates()["member"];
+    assert!(c.outdated);
+    assert!(c.local.as_ref().unwrap().modified);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crates_from_options_errors() {
+    let dir = temp_dir("errors");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            "foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "removed 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+        ],
+    );
+    let (url, _requests) = serve(
+        BTreeMap::from([(
+            String::from("/fo/ob/foobar"),
+            index_file("foobar", &[("1.0.0", false), ("1.1.0", false)]),
+        )]),
+        0,
+    );
+    let mut options = Options {
+        registry_url: Some(url),
+        ..Default::default()
+    };
+
+    // Partial results
+    let installed = Crates::from_options(&path, &[], &options).unwrap();
+    let all = installed.crates();
+    assert!(all["foobar"].outdated);
+    assert!(all["foobar"].error.is_none());
+    assert!(!all["removed"].outdated);
+    assert!(all["removed"].error.as_ref().unwrap().contains("404"));
+
+    // Strict
+    options.strict = true;
+    let e = Crates::from_options(&path, &[], &options).unwrap_err();
+    assert!(e.to_string().contains("removed"));
+    let Error::Crates(errors) = e else {
+        panic!("unexpected error: {e:?}");
+    };
+    let [Error::Crate { source, .. }] = &errors[..] else {
+        panic!("unexpected errors: {errors:?}");
+    };
+    assert!(matches!(&**source, Error::Registry { name, .. } if name == "removed"));
+
+    // Typed errors
+    assert!(matches!(
+        Crates::from(&dir.join("missing.json")),
+        Err(Error::NotFound { .. })
+    ));
+    assert!(matches!(
+        Crates::from_include(&path, &["("]),
+        Err(Error::InvalidPattern(_))
+    ));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crates_from_options_malformed() {
+    let dir = temp_dir("malformed");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            "ok 1.0.0 (path+file:///ok)",
+            "truncated",
+            "unenclosed 1.0.0 path+file:///unenclosed",
+            "éa 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+        ],
+    );
+
+    // Future format with a different rustc string and without most fields
+    let json = std::fs::read_to_string(&path).unwrap().replacen(
+        r#"{"installs":{"#,
+        r#"{"installs":{"rustc 1.0.0 (path+file:///rustc)":{"rustc":"1.85.0"},"#,
+        1,
+    );
+    std::fs::write(&path, json).unwrap();
+
+    let installed = Crates::from(&path).unwrap();
+    let all = installed.crates();
+    assert_eq!(all.len(), 5);
+    assert!(all["ok"].error.is_none());
+    for name in ["truncated", "unenclosed", "rustc", "éa"] {
+        let error = all[name].error.as_ref().unwrap();
+        assert!(error.contains("Malformed entry"), "{name}: {error}");
+    }
+    assert!(matches!(
+        latest("aébc", &None, false, None),
+        Err(Error::InvalidName { .. })
+    ));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crates_from_options_msrv() {
+    let dir = temp_dir("msrv");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            "foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "foobaz 1.1.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "fooqux 1.1.5 (registry+https://github.com/rust-lang/crates.io-index)",
+        ],
+    );
+    let index = |name: &str| {
+        [("1.0.0", "1.56"), ("1.1.0", "1.60.0"), ("1.2.0", "99.0")]
+            .iter()
+            .map(|(version, msrv)| {
+                format!(
+                    r#"{{"name":"{name}","vers":"{version}","yanked":false,"rust_version":"{msrv}"}}"#
+                ) + "\n"
+            })
+            .collect::<String>()
+    };
+    let (url, _requests) = serve(
+        BTreeMap::from([
+            (String::from("/fo/ob/foobar"), index("foobar")),
+            (String::from("/fo/ob/foobaz"), index("foobaz")),
+            (String::from("/fo/oq/fooqux"), index("fooqux")),
+        ]),
+        0,
+    );
+    let mut options = Options {
+        registry_url: Some(url),
+        ..Default::default()
+    };
+
+    // Latest and compatible versions
+    let installed = Crates::from_options(&path, &[], &options).unwrap();
+    let c = installed.crates()["foobar"];
+    assert_eq!(c.available, "1.2.0");
+    assert_eq!(c.compatible.as_deref(), Some("1.1.0"));
+    assert!(c.outdated);
+    assert!(!c.capped);
+
+    // Capped at the compatible version
+    options.msrv = true;
+    let installed = Crates::from_options(&path, &[], &options).unwrap();
+    let all = installed.crates();
+    let c = all["foobar"];
+    assert_eq!(c.available, "1.1.0");
+    assert!(c.outdated);
+    assert!(
+        c.update_command(false)
+            .join(" ")
+            .contains("--version =1.1.0")
+    );
+    assert!(!all["foobaz"].outdated);
+    assert!(!all["foobaz"].capped);
+
+    // Never capped below the installed version
+    let c = all["fooqux"];
+    assert_eq!(c.compatible.as_deref(), Some("1.1.0"));
+    assert!(!c.capped);
+    assert!(!c.outdated);
+    assert!(!c.update_command(false).contains(&String::from("--version")));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crates_from_options_yanked() {
+    let dir = temp_dir("yanked");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            "foobar 1.0.1 (registry+https://github.com/rust-lang/crates.io-index)",
+            "pinned 1.0.1 (registry+https://github.com/rust-lang/crates.io-index)",
+        ],
+    );
+    let json = std::fs::read_to_string(&path).unwrap().replacen(
+        r#""version_req":null,"bins":["pinned"]"#,
+        r#""version_req":"=1.0.1","bins":["pinned"]"#,
+        1,
+    );
+    std::fs::write(&path, json).unwrap();
+    let versions = [
+        ("1.0.0", false),
+        ("1.0.1", true),
+        ("1.1.0", false),
+        ("2.0.0", false),
+    ];
+    let (url, _requests) = serve(
+        BTreeMap::from([
+            (
+                String::from("/fo/ob/foobar"),
+                index_file("foobar", &versions),
+            ),
+            (
+                String::from("/pi/nn/pinned"),
+                index_file("pinned", &versions),
+            ),
+        ]),
+        0,
+    );
+    let options = Options {
+        registry_url: Some(url),
+        ..Default::default()
+    };
+    let installed = Crates::from_options(&path, &[], &options).unwrap();
+    let all = installed.crates();
+
+    // Updated to the latest version as usual
+    let c = all["foobar"];
+    assert!(c.installed_yanked);
+    assert!(c.outdated);
+    assert_eq!(c.available, "2.0.0");
+    assert!(!c.update_command(false).contains(&String::from("--version")));
+
+    // Moved to the nearest available version despite the pin
+    let c = all["pinned"];
+    assert!(c.error.is_none());
+    assert!(c.installed_yanked);
+    assert!(!c.outdated);
+    assert_eq!(c.replacement.as_deref(), Some("1.1.0"));
+    assert!(
+        c.update_command(false)
+            .join(" ")
+            .contains("--version =1.1.0")
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crates_from_options_advisories() {
+    let dir = temp_dir("advisories");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            "foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "foobaz 1.1.0 (registry+https://github.com/rust-lang/crates.io-index)",
+        ],
+    );
+    let versions = [("1.0.0", false), ("1.1.0", false)];
+    let (url, _requests) = serve(
+        BTreeMap::from([
+            (
+                String::from("/fo/ob/foobar"),
+                index_file("foobar", &versions),
+            ),
+            (
+                String::from("/fo/ob/foobaz"),
+                index_file("foobaz", &versions),
+            ),
+        ]),
+        0,
+    );
+
+    // Local advisory database
+    let db = dir.join("advisory-db");
+    for (name, id, extra) in [
+        ("foobar", "RUSTSEC-2025-0001", ""),
+        (
+            "foobar",
+            "RUSTSEC-2025-0002",
+            "withdrawn = \"2025-02-01\"\n",
+        ),
+        ("foobaz", "RUSTSEC-2025-0003", ""),
+    ] {
+        let crate_dir = db.join("crates").join(name);
+        std::fs::create_dir_all(&crate_dir).unwrap();
+        std::fs::write(
+            crate_dir.join(format!("{id}.md")),
+            format!(
+                "```toml\n[advisory]\nid = \"{id}\"\npackage = \"{name}\"\ndate = \"2025-01-01\"\n\
+                {extra}\n[versions]\npatched = [\">= 1.1.0\"]\n```\n\n# Problem in {name}\n"
+            ),
+        )
+        .unwrap();
+    }
+
+    let options = Options {
+        registry_url: Some(url),
+        advisory_db: Some(db),
+        ..Default::default()
+    };
+    let installed = Crates::from_options(&path, &[], &options).unwrap();
+    let all = installed.crates();
+    let advisories = &all["foobar"].advisories;
+    assert_eq!(advisories.len(), 1);
+    assert_eq!(advisories[0].id, "RUSTSEC-2025-0001");
+    assert_eq!(advisories[0].title, "Problem in foobar");
+    assert!(all["foobaz"].advisories.is_empty());
+
+    // Missing database
+    let options = Options {
+        advisory_db: Some(dir.join("missing")),
+        ..Default::default()
+    };
+    assert!(Crates::from_options(&path, &[], &options).is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn update_classify() {
+    let classify = |installed: &str, available: &str| {
+        Update::classify(
+            &semver::Version::parse(installed).unwrap(),
+            &semver::Version::parse(available).unwrap(),
+        )
+    };
+    assert_eq!(classify("1.2.3", "1.2.3"), None);
+    assert_eq!(classify("1.2.3", "1.2.4"), Some(Update::Patch));
+    assert_eq!(classify("1.2.3", "1.3.0"), Some(Update::Minor));
+    assert_eq!(classify("1.2.3", "2.0.0"), Some(Update::Major));
+    assert_eq!(classify("0.2.3", "0.2.4"), Some(Update::Patch));
+    assert_eq!(classify("0.2.3", "0.3.0"), Some(Update::Major));
+    assert_eq!(classify("0.0.2", "0.0.3"), Some(Update::Major));
+    assert_eq!(classify("1.2.3", "2.0.0-rc.1"), Some(Update::Prerelease));
+    assert_eq!(classify("1.2.3", "1.2.2"), Some(Update::Ahead));
+}
+
+#[test]
+fn crates_from_options_ahead() {
+    let dir = temp_dir("ahead");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &["foobar 1.2.0 (registry+https://github.com/rust-lang/crates.io-index)"],
+    );
+    let (url, _requests) = serve(
+        BTreeMap::from([(
+            String::from("/fo/ob/foobar"),
+            index_file("foobar", &[("1.0.0", false), ("1.1.0", false)]),
+        )]),
+        0,
+    );
+    let options = Options {
+        registry_url: Some(url),
+        ..Default::default()
+    };
+    let installed = Crates::from_options(&path, &[], &options).unwrap();
+    let c = installed.crates()["foobar"];
+    assert_eq!(c.update, Some(Update::Ahead));
+    assert!(!c.outdated);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crates_from_options_config() {
+    let dir = temp_dir("config");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            "foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "barbaz 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "bazqux 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+        ],
+    );
+    let (url, _requests) = serve(
+        BTreeMap::from([
+            (
+                String::from("/fo/ob/foobar"),
+                index_file(
+                    "foobar",
+                    &[("1.0.0", false), ("1.0.1", false), ("2.0.0", false)],
+                ),
+            ),
+            (
+                String::from("/ba/rb/barbaz"),
+                index_file("barbaz", &[("1.0.0", false), ("1.1.0-rc.1", false)]),
+            ),
+        ]),
+        0,
+    );
+    let config_path = dir.join("config.toml");
+    std::fs::write(
+        &config_path,
+        "\
+outdated = true
+locked = true
+
+[registry]
+retries = 1
+offline = true
+
+[crates.foobar]
+hold = \"~1.0\"
+
+[crates.barbaz]
+prerelease = true
+
+[crates.bazqux]
+ignore = true
+",
+    )
+    .unwrap();
+    let config = Config::from_path(&config_path).unwrap();
+    assert_eq!(config.outdated, Some(true));
+    assert_eq!(config.registry.retries, Some(1));
+
+    // The caller overrides the config file
+    let options = config.options();
+    assert_eq!(options.retries, 1);
+    assert!(options.locked);
+    assert!(options.offline);
+    let mut options = Options {
+        registry_url: Some(url),
+        offline: false,
+        locked: false,
+        ..config.options()
+    };
+    assert_eq!(options.retries, 1);
+    assert!(!options.locked);
+    options.policies.insert(
+        String::from("other"),
+        Policy {
+            ignore: true,
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        options.policies.keys().collect::<Vec<_>>(),
+        ["barbaz", "bazqux", "foobar", "other"]
+    );
+    let installed = Crates::from_options(&path, &[], &options).unwrap();
+    let crates = installed.crates();
+    assert!(!crates.contains_key("bazqux"));
+
+    let c = crates["foobar"];
+    assert_eq!(c.available, "1.0.1");
+    assert_eq!(c.hold.as_deref(), Some("~1.0"));
+    assert!(c.update_command(true).contains(&String::from("~1.0")));
+
+    let c = crates["barbaz"];
+    assert_eq!(c.available, "1.1.0-rc.1");
+    assert!(c.outdated);
+
+    std::fs::write(&config_path, "unknown = 1\n").unwrap();
+    assert!(matches!(
+        Config::from_path(&config_path),
+        Err(Error::InvalidConfig { .. })
+    ));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crates_from_filters() {
+    let dir = temp_dir("filters");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            "foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "foobaz 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "barbaz 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+        ],
+    );
+    let (url, _requests) = serve(
+        BTreeMap::from([
+            (
+                String::from("/fo/ob/foobar"),
+                index_file("foobar", &[("1.0.0", false)]),
+            ),
+            (
+                String::from("/fo/ob/foobaz"),
+                index_file("foobaz", &[("1.0.0", false)]),
+            ),
+            (
+                String::from("/ba/rb/barbaz"),
+                index_file("barbaz", &[("1.0.0", false)]),
+            ),
+        ]),
+        0,
+    );
+    let names = |include: &[&str], exclude: &[&str]| {
+        let options = Options {
+            registry_url: Some(url.clone()),
+            exclude: exclude.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        };
+        Crates::from_options(&path, include, &options)
+            .unwrap()
+            .crates()
+            .into_keys()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&[], &["^foo"]), ["barbaz"]);
+    assert_eq!(names(&["^foo"], &["baz$"]), ["foobar"]);
+    assert_eq!(names(&[], &["r$", "z$"]), Vec::<String>::new());
+
+    assert!(matches!(
+        Crates::from_filters(&path, &[], &["("]),
+        Err(Error::InvalidPattern(_))
+    ));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crates_installer() {
+    let dir = temp_dir("installer");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            "barbaz 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "bazqux 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "gitbar 0.1.0 (git+https://example.com/gitbar?branch=main#0123abc)",
+        ],
+    );
+    let text = std::fs::read_to_string(&path).unwrap().replacen(
+        r#""features":[],"#,
+        r#""features":["a"],"#,
+        1,
+    );
+    std::fs::write(&path, text).unwrap();
+
+    let options = Options {
+        registry_url: Some(String::from("http://127.0.0.1:1/")),
+        installer: Installer::Binstall,
+        policies: BTreeMap::from([(
+            String::from("bazqux"),
+            Policy {
+                installer: Some(Installer::Cargo),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    };
+    let installed = Crates::parse(&path, &[], &options).unwrap();
+    let crates = installed.crates();
+
+    // Features cannot be honored by cargo binstall
+    assert_eq!(crates["barbaz"].installer, Installer::Cargo);
+    assert_eq!(crates["bazqux"].installer, Installer::Cargo);
+    assert_eq!(crates["gitbar"].installer, Installer::Cargo);
+
+    let c = crates["foobar"];
+    assert_eq!(c.installer, Installer::Binstall);
+    let root = dir.canonicalize().unwrap().display().to_string();
+    assert_eq!(
+        c.update_command(false),
+        [
+            "cargo",
+            "binstall",
+            "--no-confirm",
+            "--targets",
+            "x86_64-unknown-linux-gnu",
+            "--root",
+            &root,
+            "foobar",
+        ]
+    );
+    assert_eq!(
+        crates["bazqux"].update_command(false)[..2],
+        ["cargo", "install"]
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crates_update_command() {
+    let dir = temp_dir("update-command");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            "foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "gitbar 0.1.0 (git+https://example.com/gitbar?branch=main#0123abc)",
+        ],
+    );
+    let text = std::fs::read_to_string(&path)
+        .unwrap()
+        .replacen(r#""bins":["foobar"]"#, r#""bins":["foo","bar"]"#, 1)
+        .replacen(r#""all_features":false"#, r#""all_features":true"#, 1);
+    std::fs::write(&path, text).unwrap();
+
+    let options = Options {
+        registry_url: Some(String::from("http://127.0.0.1:1/")),
+        locked: true,
+        policies: BTreeMap::from([(
+            String::from("gitbar"),
+            Policy {
+                locked: Some(false),
+                all_bins: Some(true),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    };
+    let installed = Crates::parse(&path, &[], &options).unwrap();
+    let crates = installed.crates();
+    let root = dir.canonicalize().unwrap().display().to_string();
+
+    let c = crates["foobar"];
+    assert!(c.locked);
+    assert_eq!(
+        c.update_command(false),
+        [
+            "cargo",
+            "install",
+            "--all-features",
+            "--profile",
+            "release",
+            "--target",
+            "x86_64-unknown-linux-gnu",
+            "--locked",
+            "--root",
+            &root,
+            "--bin",
+            "foo",
+            "--bin",
+            "bar",
+            "foobar",
+        ]
+    );
+
+    // Package name after the git source
+    let c = crates["gitbar"];
+    assert!(!c.locked);
+    assert!(c.all_bins);
+    assert!(c.update_command(false).join(" ").ends_with(&format!(
+        "--root {root} --git https://example.com/gitbar --branch main gitbar"
+    )));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn manifest_export() {
+    let dir = temp_dir("manifest");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            "foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "gitbar 0.1.0 (git+https://example.com/gitbar?branch=main#0123abc)",
+            "broken 1.0.0",
+        ],
+    );
+    let text = std::fs::read_to_string(&path).unwrap().replacen(
+        r#""features":[],"#,
+        r#""features":["a","b"],"#,
+        1,
+    );
+    std::fs::write(&path, text).unwrap();
+
+    // No registry access
+    let options = Options {
+        registry_url: Some(String::from("http://127.0.0.1:1/")),
+        ..Default::default()
+    };
+    let installed = Crates::parse(&path, &[], &options).unwrap();
+    assert!(installed.crates()["broken"].error.is_some());
+
+    let manifest = Manifest::from(&installed);
+    assert_eq!(manifest.version, MANIFEST_VERSION);
+    assert_eq!(
+        manifest.crates.keys().collect::<Vec<_>>(),
+        ["foobar", "gitbar"]
+    );
+    let foobar = &manifest.crates["foobar"];
+    assert_eq!(foobar.version, "1.0.0");
+    assert_eq!(foobar.features, ["a", "b"]);
+    assert_eq!(foobar.kind(), Kind::External);
+    let gitbar = &manifest.crates["gitbar"];
+    assert_eq!(gitbar.rev.as_deref(), Some("0123abc"));
+    assert_eq!(gitbar.kind(), Kind::Git);
+
+    let toml = manifest.to_toml().unwrap();
+    assert!(toml.starts_with("version = 1\n"));
+    assert_eq!(toml::from_str::<Manifest>(&toml).unwrap(), manifest);
+    assert_eq!(
+        serde_json::from_str::<Manifest>(&manifest.to_json().unwrap()).unwrap(),
+        manifest
+    );
+
+    let sh = manifest.to_sh().unwrap();
+    assert!(sh.starts_with("#!/bin/sh\n"));
+    assert!(sh.contains("cargo install -F a,b --version =1.0.0 "));
+    assert!(sh.contains("--git https://example.com/gitbar --rev 0123abc"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn manifest_plan() {
+    let dir = temp_dir("plan");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            "foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "barbaz 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "extra 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+        ],
+    );
+    let installed = Crates::parse(&path, &[], &Options::default()).unwrap();
+
+    let mut manifest = Manifest::from(&installed);
+    assert!(manifest.plan(&installed, true).unwrap().is_empty());
+
+    manifest.crates.remove("extra");
+    manifest.crates.get_mut("foobar").unwrap().version = String::from("1.1.0");
+    manifest.crates.get_mut("barbaz").unwrap().features = vec![String::from("x")];
+    let mut new = manifest.crates["foobar"].clone();
+    new.version = String::from("2.0.0");
+    manifest.crates.insert(String::from("new"), new);
+
+    let manifest_path = dir.join("manifest.json");
+    std::fs::write(&manifest_path, manifest.to_json().unwrap()).unwrap();
+    let manifest = Manifest::from_path(&manifest_path).unwrap();
+
+    let steps = manifest.plan(&installed, false).unwrap();
+    let summary = steps
+        .iter()
+        .map(|x| (x.name.as_str(), x.action, x.differences.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        [
+            ("barbaz", Action::Reinstall, vec!["features"]),
+            ("foobar", Action::Reinstall, vec!["version"]),
+            ("new", Action::Install, vec![]),
+        ]
+    );
+    assert!(steps[1].command.contains(&String::from("--force")));
+    assert!(steps[1].command.contains(&String::from("=1.1.0")));
+    assert!(!steps[2].command.contains(&String::from("--force")));
+    assert_eq!(steps[2].kind, Kind::External);
+
+    // Installation root of the metadata file
+    let root = dir.canonicalize().unwrap().display().to_string();
+    assert!(
+        steps[2]
+            .command
+            .join(" ")
+            .contains(&format!("--root {root}"))
+    );
+    let steps = manifest.plan(&installed, true).unwrap();
+    assert_eq!(steps.last().unwrap().action, Action::Uninstall);
+    assert_eq!(
+        steps.last().unwrap().command,
+        ["cargo", "uninstall", "--root", &root, "extra"]
+    );
+
+    std::fs::write(&manifest_path, r#"{"version":99,"crates":{}}"#).unwrap();
+    assert!(matches!(
+        Manifest::from_path(&manifest_path),
+        Err(Error::UnsupportedManifest { version: 99, .. })
+    ));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn manifest_diff() {
+    let dir = temp_dir("diff");
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            "foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "barbaz 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "left 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+        ],
+    );
+    let left = Manifest::load(&path).unwrap();
+    assert_eq!(left.crates.len(), 3);
+
+    let mut right = left.clone();
+    right.crates.remove("left");
+    right.crates.get_mut("foobar").unwrap().profile = String::from("dev");
+    right.crates.get_mut("barbaz").unwrap().rustc = String::from("rustc 1.86.0");
+    let mut new = right.crates["foobar"].clone();
+    new.version = String::from("2.0.0");
+    right.crates.insert(String::from("right"), new);
+    let right_path = dir.join("right.toml");
+    std::fs::write(&right_path, right.to_toml().unwrap()).unwrap();
+    let right = Manifest::load(&right_path).unwrap();
+
+    let differences = left.diff(&right);
+    let summary = differences
+        .iter()
+        .map(|x| {
+            (
+                x.name.as_str(),
+                x.left.is_some(),
+                x.right.is_some(),
+                x.fields.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        [
+            ("barbaz", true, true, vec!["rustc"]),
+            ("foobar", true, true, vec!["profile"]),
+            ("left", true, false, vec![]),
+            ("right", false, true, vec![]),
+        ]
+    );
+    assert!(left.diff(&left).is_empty());
+
+    // Values of the differing fields
+    let foobar = &differences[1];
+    assert_eq!(foobar.left.as_ref().unwrap().value("profile"), "release");
+    assert_eq!(foobar.right.as_ref().unwrap().value("profile"), "dev");
+    assert_eq!(
+        differences[0].right.as_ref().unwrap().value("rustc"),
+        "rustc 1.86.0"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn manifest_rollback() {
+    let dir = temp_dir("rollback");
+    let snapshots = dir.join("snapshots");
+    assert!(matches!(
+        Manifest::rollback(&snapshots, None),
+        Err(Error::NoSnapshot { name: None })
+    ));
+
+    let path = dir.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            "foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "barbaz 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+        ],
+    );
+    let first = Manifest::from(&Crates::parse(&path, &[], &Options::default()).unwrap());
+    first.save_snapshot(&snapshots).unwrap();
+    std::thread::sleep(Duration::from_millis(2));
+
+    let mut second = first.clone();
+    second.crates.remove("barbaz");
+    second.crates.get_mut("foobar").unwrap().version = String::from("1.1.0");
+    let local = dir.join("local.json");
+    write_crates2(&local, &["localbar 0.1.0 (path+file:///tmp/localbar)"]);
+    let mut with_local = second.clone();
+    with_local
+        .crates
+        .extend(Manifest::from(&Crates::parse(&local, &[], &Options::default()).unwrap()).crates);
+    with_local.save_snapshot(&snapshots).unwrap();
+    assert_eq!(Manifest::snapshots(&snapshots).unwrap().len(), 2);
+
+    // Last run (local crates cannot be restored)
+    let manifest = Manifest::rollback(&snapshots, None).unwrap();
+    assert_eq!(manifest, second);
+    assert!(matches!(
+        Manifest::rollback(&snapshots, Some("localbar")),
+        Err(Error::NoSnapshot { name: Some(_) })
+    ));
+    let commands = manifest.install_commands(None).unwrap();
+    assert!(commands["foobar"].contains(&String::from("=1.1.0")));
+    assert!(!commands["foobar"].contains(&String::from("--root")));
+    assert!(commands["foobar"].ends_with(&[
+        String::from("--bin"),
+        String::from("foobar"),
+        String::from("foobar"),
+    ]));
+
+    // Latest snapshot containing the crate
+    let manifest = Manifest::rollback(&snapshots, Some("barbaz")).unwrap();
+    assert_eq!(manifest.crates.keys().collect::<Vec<_>>(), ["barbaz"]);
+    assert_eq!(manifest.crates["barbaz"].version, "1.0.0");
+    assert!(matches!(
+        Manifest::rollback(&snapshots, Some("nope")),
+        Err(Error::NoSnapshot { name: Some(_) })
+    ));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Job running a shell script
+fn sh_job(name: &str, script: &str) -> Job {
+    Job {
+        name: name.to_string(),
+        kind: Kind::External,
+        command: vec![String::from("sh"), String::from("-c"), script.to_string()],
+    }
+}
+
+#[test]
+fn runner_fail_fast() {
+    let jobs = [
+        sh_job("ok", "echo err >&2"),
+        sh_job("fail", "echo first >&2; echo oops >&2; exit 3"),
+        sh_job("later", "true"),
+    ];
+
+    // Keep going by default
+    let mut results = vec![];
+    Runner::default().run(&jobs, &mut results, |_| {}).unwrap();
+    assert_eq!(
+        results.iter().map(|x| x.status).collect::<Vec<_>>(),
+        [Status::Ok, Status::Failed, Status::Ok]
+    );
+    assert_eq!(results[0].tail, ["err"]);
+    assert_eq!(results[1].tail, ["first", "oops"]);
+    assert!(results.iter().all(|x| x.log.is_none()));
+    assert!(matches!(
+        Runner::check(&results),
+        Err(Error::Failed { failed: 1 })
+    ));
+
+    // Skip the jobs after a failure
+    let runner = Runner {
+        fail_fast: true,
+        ..Default::default()
+    };
+    let started = Mutex::new(vec![]);
+    let mut results = vec![];
+    runner
+        .run(&jobs, &mut results, |x| {
+            if let Event::Start(job) = x {
+                started.lock().unwrap().push(job.name.clone());
+            }
+        })
+        .unwrap();
+    assert_eq!(
+        results.iter().map(|x| x.status).collect::<Vec<_>>(),
+        [Status::Ok, Status::Failed, Status::Skipped]
+    );
+    assert_eq!(*started.lock().unwrap(), ["ok", "fail"]);
+
+    // Skip all jobs after an earlier failure
+    runner
+        .run(&[sh_job("next", "true")], &mut results, |_| {})
+        .unwrap();
+    assert_eq!(results[3].status, Status::Skipped);
+    assert!(matches!(
+        Runner::check(&results),
+        Err(Error::Failed { failed: 1 })
+    ));
+    assert!(Runner::check(&results[..1]).is_ok());
+
+    // Command that cannot be run
+    let mut results = vec![];
+    Runner::default()
+        .run(
+            &[Job {
+                name: String::from("missing"),
+                kind: Kind::External,
+                command: vec![String::from("/nonexistent/cargo-list-test")],
+            }],
+            &mut results,
+            |_| {},
+        )
+        .unwrap();
+    assert!(results[0].failed());
+    assert!(results[0].tail[0].starts_with("Failed to run"));
+}
+
+#[test]
+fn runner_concurrent() {
+    let dir = temp_dir("runner");
+    let logs = dir.join("logs");
+
+    // Each job waits (up to a minute) for the marker of the other, so both must run at once
+    let wait = |mine: &str, other: &str| {
+        format!(
+            "touch '{}'; i=0; while [ ! -e '{}' ]; do i=$((i + 1)); \
+            [ $i -gt 600 ] && exit 2; sleep 0.1; done",
+            dir.join(mine).display(),
+            dir.join(other).display(),
+        )
+    };
+    let jobs = [
+        sh_job(
+            "first",
+            &format!("{}; echo out; echo err >&2", wait("first", "second")),
+        ),
+        sh_job(
+            "second",
+            &format!("{}; echo failed >&2; exit 1", wait("second", "first")),
+        ),
+    ];
+    let runner = Runner {
+        jobs: 2,
+        log_dir: Some(logs.clone()),
+        ..Default::default()
+    };
+    let finished = Mutex::new(vec![]);
+    let mut results = vec![];
+    runner
+        .run(&jobs, &mut results, |x| {
+            if let Event::Finish(result) = x {
+                finished.lock().unwrap().push(result.name.clone());
+            }
+        })
+        .unwrap();
+
+    assert_eq!(finished.lock().unwrap().len(), 2);
+
+    // Results are in the order of the jobs with the output in their log files
+    assert_eq!(
+        results.iter().map(|x| x.name.as_str()).collect::<Vec<_>>(),
+        ["first", "second"]
+    );
+    assert_eq!(results[0].status, Status::Ok);
+    assert_eq!(results[0].log.as_ref(), Some(&logs.join("first.log")));
+    assert_eq!(
+        std::fs::read_to_string(logs.join("first.log")).unwrap(),
+        "out\nerr\n"
+    );
+    assert_eq!(results[0].tail, ["out", "err"]);
+    assert!(results[1].failed());
+    assert_eq!(results[1].tail, ["failed"]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn runner_target_dir() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("target-dir");
+    let logs = dir.join("logs");
+    let stub = dir.join("cargo");
+    std::fs::write(
+        &stub,
+        "#!/bin/sh\necho \"$@\"\necho \"$CARGO_TARGET_DIR\"\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&stub, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let job = |name: &str, subcommand: &str| Job {
+        name: name.to_string(),
+        kind: Kind::External,
+        command: vec![
+            stub.display().to_string(),
+            subcommand.to_string(),
+            name.to_string(),
+        ],
+    };
+    let jobs = [job("foobar", "install"), job("barbaz", "binstall")];
+
+    // Temporary target directory for each job
+    let runner = Runner {
+        jobs: 2,
+        log_dir: Some(logs.clone()),
+        ..Default::default()
+    };
+    let mut results = vec![];
+    runner.run(&jobs, &mut results, |_| {}).unwrap();
+    let target = logs.join("foobar").display().to_string();
+    assert_eq!(
+        results[0].tail[0],
+        format!("install --target-dir {target} foobar")
+    );
+    assert_eq!(
+        results[1].tail,
+        [
+            String::from("binstall barbaz"),
+            logs.join("barbaz").display().to_string()
+        ]
+    );
+    assert!(!logs.join("foobar").exists());
+
+    // Shared target directory
+    let shared = dir.join("target");
+    let runner = Runner {
+        target_dir: Some(shared.clone()),
+        ..runner
+    };
+    let mut results = vec![];
+    runner.run(&jobs[..1], &mut results, |_| {}).unwrap();
+    assert_eq!(
+        results[0].tail[0],
+        format!("install --target-dir {} foobar", shared.display())
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
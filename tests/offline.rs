@@ -0,0 +1,63 @@
+mod common;
+
+use {
+    cargo_list::{Crates, Options},
+    common::{temp_dir, write_crates2, write_index_cache},
+};
+
+// This test sets `CARGO_HOME` so it lives in its own test binary (process)
+
+#[test]
+fn crates_from_options_offline() {
+    let home = temp_dir("offline");
+    let index = home.join("registry/index");
+    write_index_cache(
+        &index.join("index.crates.io-0000000000000000"),
+        "foobar",
+        &[("1.0.0", false), ("1.2.0", true), ("1.1.0", false)],
+    );
+    write_index_cache(
+        &index.join("my.registry-0000000000000000"),
+        "internal",
+        &[("0.1.0", false), ("0.2.0", false)],
+    );
+    std::fs::write(
+        home.join("config.toml"),
+        "[registries]\nmy = { index = \"sparse+https://my.registry/index/\" }\n",
+    )
+    .unwrap();
+    let path = home.join(".crates2.json");
+    write_crates2(
+        &path,
+        &[
+            "foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)",
+            "internal 0.1.0 (sparse+https://my.registry/index/)",
+        ],
+    );
+
+    // SAFETY: this is the only test in this binary
+    unsafe { std::env::set_var("CARGO_HOME", &home) };
+    let installed = Crates::from_options(
+        &path,
+        &[],
+        &Options {
+            offline: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    std::fs::remove_dir_all(&home).unwrap();
+
+    let all = installed.crates();
+
+    let c = all["foobar"];
+    assert_eq!(c.available, "1.1.0");
+    assert!(c.outdated);
+    assert!(c.stale);
+    assert!(c.registry.is_none());
+
+    let c = all["internal"];
+    assert_eq!(c.available, "0.2.0");
+    assert_eq!(c.registry.as_ref().unwrap().name.as_deref(), Some("my"));
+    assert!(c.update_command(false).join(" ").contains("--registry my "));
+}